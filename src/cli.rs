@@ -0,0 +1,245 @@
+use crate::{
+    bytes_to_human_readable,
+    export::{self, ExportFormat},
+    format_duration, index_cache, manifest,
+    mirror::{self, Previous},
+    parser::parser::{Asset, AssetFetcher},
+    revision_checker::Revision,
+    settings::Settings,
+    store::ObjectStore,
+    watch::{self, WatchOptions},
+    AssetList, VERSION,
+};
+use anyhow::{anyhow, bail, Result};
+use clap::{Parser, Subcommand};
+use std::{path::PathBuf, time::Duration};
+
+/// Without a command, the interactive asset browser is started
+#[derive(Debug, Parser)]
+#[command(name = "arcane", version = VERSION, about = "Browse and fetch Wizard101 assets")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Export the file list of the latest revision to JSON, CSV or SQLite
+    Export {
+        #[arg(short, long, value_enum, default_value_t = ExportFormat::Json)]
+        format: ExportFormat,
+        /// Defaults to `exports/<revision>.<format>`
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Only export the assets matching this search, using the syntax of the search box
+        #[arg(short, long)]
+        query: Option<String>,
+        /// Use the cached file list instead of connecting to the patch server
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Download every asset of the latest revision into `assets/<revision>/`, skipping files which are verified already
+    Mirror {
+        /// Use the cached file list instead of asking the patch server for the latest revision
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Like `mirror`, but files which did not change since an earlier revision are hard-linked from its folder instead of downloaded
+    Sync {
+        /// Revision to link unchanged files from, defaults to the most recent one with downloaded assets
+        #[arg(long)]
+        from: Option<String>,
+        /// Use the cached file list instead of asking the patch server for the latest revision
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Keep checking for new revisions, reporting what changed. Options default to the `watch` section of the settings.
+    Watch {
+        /// Seconds between two checks
+        #[arg(short, long)]
+        interval: Option<u64>,
+        /// Download every added or changed asset of a new revision
+        #[arg(short, long)]
+        download: bool,
+        /// Shell command to run for every new revision, which receives the event as JSON on stdin
+        #[arg(long)]
+        hook: Option<String>,
+        /// URL to POST the event of every new revision to as JSON
+        #[arg(long)]
+        webhook: Option<String>,
+    },
+    /// Re-check the downloaded assets of a revision against its manifest and the file list
+    Verify {
+        /// Revision to check, using its cached file list. Defaults to the latest revision.
+        #[arg(long)]
+        revision: Option<String>,
+        /// Use the cached file list instead of asking the patch server for the latest revision
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Delete every object of the object store which no kept revision refers to
+    Gc {
+        /// Revisions to keep, all others are dropped together with their `assets/<revision>/` folder. Defaults to every revision.
+        #[arg(long, num_args = 1..)]
+        keep: Option<Vec<String>>,
+        /// Only report what would be deleted
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+pub async fn run(command: Command, settings: &Settings) -> Result<()> {
+    match command {
+        Command::Export {
+            format,
+            output,
+            query,
+            offline,
+        } => {
+            let fetcher = load_fetcher(offline, settings).await?;
+            let mut assets = AssetList::with_items(fetcher.assets.clone());
+            if let Some(query) = &query {
+                assets.filter_and_sort(query)?;
+            }
+
+            let revision = fetcher.to_revision();
+            let path = output.unwrap_or_else(|| export::default_path(&revision.revision, format, query.is_some()));
+            export::export(&assets.filtered_items, &revision, format, &path)?;
+
+            println!(
+                "Exported {} assets of {} to {}",
+                assets.filtered_items.len(),
+                revision.revision,
+                path.display()
+            );
+            Ok(())
+        }
+        Command::Mirror { offline } => {
+            let fetcher = load_fetcher(offline, settings).await?;
+            run_mirror(&fetcher, None).await
+        }
+        Command::Sync { from, offline } => {
+            let fetcher = load_fetcher(offline, settings).await?;
+            let previous = match from {
+                Some(revision) => Previous::load(&revision)?,
+                None => Previous::find(&fetcher.revision)?,
+            };
+
+            run_mirror(&fetcher, Some(&previous)).await
+        }
+        Command::Watch {
+            interval,
+            download,
+            hook,
+            webhook,
+        } => {
+            let mut options = WatchOptions::new(&settings.watch);
+            if let Some(interval) = interval {
+                options.interval = Duration::from_secs(interval);
+            }
+            options.download |= download;
+            options.hook = hook.or(options.hook);
+            options.webhook = webhook.or(options.webhook);
+
+            watch::watch(&options, settings).await
+        }
+        Command::Verify { revision, offline } => {
+            let fetcher = match revision {
+                Some(revision) => load_cached(&revision)?,
+                None => load_index(offline).await?,
+            };
+
+            let (revision, assets) = (fetcher.revision.clone(), fetcher.assets.iter().cloned().collect::<Vec<Asset>>());
+            let total = assets.len();
+            let report = tokio::task::spawn_blocking(move || {
+                manifest::audit(&revision, &assets, |checked| eprint!("\r{checked}/{total} assets checked"))
+            })
+            .await??;
+            eprintln!();
+
+            println!("{}", report.summary());
+            for (filename, finding) in &report.failed {
+                println!("  {filename}: {finding}");
+            }
+
+            if !report.failed.is_empty() {
+                bail!("{} assets failed verification", report.failed.len());
+            }
+            Ok(())
+        }
+        Command::Gc { keep, dry_run } => {
+            let report = ObjectStore::open().gc(keep.as_deref(), dry_run)?;
+            let verb = match dry_run {
+                true => "Would remove",
+                false => "Removed",
+            };
+
+            for revision in &report.dropped {
+                println!("{verb} revision {revision}");
+            }
+            println!(
+                "{verb} {} objects ({}), {} objects of {} revisions remain",
+                report.removed,
+                bytes_to_human_readable(report.freed as i64),
+                report.remaining,
+                report.kept.len()
+            );
+            Ok(())
+        }
+    }
+}
+
+async fn run_mirror(fetcher: &AssetFetcher, previous: Option<&Previous>) -> Result<()> {
+    let assets: Vec<Asset> = fetcher.assets.iter().cloned().collect();
+    mirror::check_space(fetcher, &assets, previous)?;
+    if let Some(schedule) = fetcher.schedule().filter(|schedule| !schedule.is_open()) {
+        eprintln!("Waiting for the download window {schedule}");
+    }
+
+    let report = mirror::mirror(fetcher, &assets, previous, |report| {
+        eprint!("\r{}/{} assets ({} failed)", report.done(), report.total, report.failed.len());
+    })
+    .await;
+    eprintln!();
+
+    println!("{}", report.summary());
+    for (filename, reason) in &report.failed {
+        println!("  {filename}: {reason}");
+    }
+
+    if !report.failed.is_empty() {
+        bail!("{} assets could not be mirrored", report.failed.len());
+    }
+    Ok(())
+}
+
+/// Loads the file list of the latest revision, or the most recently cached one if `offline` is set
+pub async fn load_fetcher(offline: bool, settings: &Settings) -> Result<AssetFetcher> {
+    let mut fetcher = load_index(offline).await?;
+    fetcher.configure(settings)?;
+    Ok(fetcher)
+}
+
+async fn load_index(offline: bool) -> Result<AssetFetcher> {
+    if offline {
+        let revision = index_cache::latest()?.ok_or_else(|| anyhow!("No cached file list found, connect at least once first"))?;
+        let age = index_cache::age(&revision).map(format_duration).unwrap_or_default();
+        eprintln!("Using the cached file list of {revision} ({age} old)");
+
+        return load_cached(&revision);
+    }
+
+    let revision = Revision::check().await?;
+    eprintln!("Loading the file list of {}", revision.revision);
+
+    let mut fetcher = AssetFetcher::new(revision);
+    fetcher.load_index().await?;
+    Ok(fetcher)
+}
+
+fn load_cached(revision: &str) -> Result<AssetFetcher> {
+    let (revision, xml_text) = index_cache::load(revision).map_err(|e| anyhow!("No cached file list of {revision}: {e}"))?;
+    let mut fetcher = AssetFetcher::new(revision);
+    fetcher.parse_index(xml_text)?;
+    Ok(fetcher)
+}
@@ -0,0 +1,193 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How often the throughput is sampled for the status bar
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// Number of throughput samples kept for the sparkline
+const HISTORY_LEN: usize = 120;
+/// Number of samples the current throughput is averaged over
+const THROUGHPUT_WINDOW: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct DownloadProgress {
+    pub total: u64,
+    pub received: u64,
+    pub chunks: usize,
+    pub state: DownloadState,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadState {
+    /// Waiting for a free slot in the download queue
+    Queued,
+    Running,
+    Finished,
+    Failed(String),
+}
+
+/// Aggregated progress of every running download
+#[derive(Debug, Clone, Default)]
+pub struct DownloadSummary {
+    pub active: usize,
+    pub queued: usize,
+    pub total: u64,
+    pub received: u64,
+    /// Bytes per second, averaged over the last few samples
+    pub throughput: u64,
+    pub eta: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct TrackerState {
+    downloads: HashMap<String, DownloadProgress>,
+    /// Bytes received since the last sample was taken
+    received_since_sample: u64,
+    last_sample: Instant,
+    history: VecDeque<u64>,
+}
+
+/// Keeps track of the progress of all downloads. Cloning it is cheap, every clone shares the same state.
+#[derive(Debug, Clone)]
+pub struct DownloadTracker {
+    state: Arc<Mutex<TrackerState>>,
+}
+
+impl Default for DownloadTracker {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(TrackerState {
+                downloads: HashMap::new(),
+                received_since_sample: 0,
+                last_sample: Instant::now(),
+                history: VecDeque::with_capacity(HISTORY_LEN),
+            })),
+        }
+    }
+}
+
+impl DownloadProgress {
+    pub fn ratio(&self) -> f64 {
+        match self.total {
+            0 => 0.0,
+            total => (self.received as f64 / total as f64).clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl DownloadTracker {
+    pub fn queue(&self, filename: &str, total: u64) {
+        self.insert(filename, total, DownloadState::Queued);
+    }
+
+    pub fn start(&self, filename: &str, total: u64) {
+        self.insert(filename, total, DownloadState::Running);
+    }
+
+    /// Continues a download which already has `received` of its `total` bytes
+    pub fn resume(&self, filename: &str, total: u64, received: u64) {
+        let progress = DownloadProgress {
+            total,
+            received,
+            chunks: 0,
+            state: DownloadState::Running,
+        };
+
+        self.state.lock().unwrap().downloads.insert(filename.to_string(), progress);
+    }
+
+    fn insert(&self, filename: &str, total: u64, download_state: DownloadState) {
+        let progress = DownloadProgress {
+            total,
+            received: 0,
+            chunks: 0,
+            state: download_state,
+        };
+
+        self.state.lock().unwrap().downloads.insert(filename.to_string(), progress);
+    }
+
+    /// Forgets about a queued file which did not need to be downloaded after all
+    pub fn remove(&self, filename: &str) {
+        self.state.lock().unwrap().downloads.remove(filename);
+    }
+
+    /// Called by `write_to_file_chunked` for every chunk written to disk
+    pub fn add_chunk(&self, filename: &str, len: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.received_since_sample += len;
+
+        if let Some(progress) = state.downloads.get_mut(filename) {
+            progress.received += len;
+            progress.chunks += 1;
+        }
+    }
+
+    pub fn finish(&self, filename: &str) {
+        self.set_state(filename, DownloadState::Finished);
+    }
+
+    pub fn fail(&self, filename: &str, reason: impl ToString) {
+        self.set_state(filename, DownloadState::Failed(reason.to_string()));
+    }
+
+    fn set_state(&self, filename: &str, download_state: DownloadState) {
+        if let Some(progress) = self.state.lock().unwrap().downloads.get_mut(filename) {
+            progress.state = download_state;
+        }
+    }
+
+    pub fn get(&self, filename: &str) -> Option<DownloadProgress> {
+        self.state.lock().unwrap().downloads.get(filename).cloned()
+    }
+
+    /// Takes a throughput sample once per `SAMPLE_INTERVAL`, should be called on every tick
+    pub fn sample(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.last_sample.elapsed() < SAMPLE_INTERVAL {
+            return;
+        }
+
+        let bytes_per_sec = (state.received_since_sample as f64 / state.last_sample.elapsed().as_secs_f64()) as u64;
+        if state.history.len() == HISTORY_LEN {
+            state.history.pop_front();
+        }
+
+        state.history.push_back(bytes_per_sec);
+        state.received_since_sample = 0;
+        state.last_sample = Instant::now();
+    }
+
+    /// Throughput samples (bytes/sec), oldest first
+    pub fn history(&self) -> Vec<u64> {
+        self.state.lock().unwrap().history.iter().copied().collect()
+    }
+
+    pub fn summary(&self) -> DownloadSummary {
+        let state = self.state.lock().unwrap();
+        let mut summary = DownloadSummary::default();
+
+        for progress in state.downloads.values() {
+            match progress.state {
+                DownloadState::Running => summary.active += 1,
+                DownloadState::Queued => summary.queued += 1,
+                DownloadState::Finished | DownloadState::Failed(_) => continue,
+            }
+
+            summary.total += progress.total;
+            summary.received += progress.received;
+        }
+
+        let window = state.history.len().min(THROUGHPUT_WINDOW);
+        if window > 0 {
+            summary.throughput = state.history.iter().rev().take(window).sum::<u64>() / window as u64;
+        }
+
+        let remaining = summary.total.saturating_sub(summary.received);
+        summary.eta = remaining.checked_div(summary.throughput).map(Duration::from_secs);
+
+        summary
+    }
+}
@@ -9,3 +9,157 @@ pub enum RevisionError {
     #[error("Expected SERVICE_ID=8 & MESSAGE_ID=2 but got {0} & {1}")]
     InvalidProtocol(u8, u8),
 }
+
+#[derive(Debug, Error)]
+pub enum QueryError {
+    #[error("Invalid size \"{0}\"")]
+    InvalidSize(String),
+    #[error("Invalid CRC \"{0}\"")]
+    InvalidCrc(String),
+    #[error("Expected yes or no but got \"{0}\"")]
+    InvalidBool(String),
+    #[error("Invalid regex: {0}")]
+    InvalidRegex(#[from] regex::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum IndexError {
+    #[error("Failed to request the file list: {0}")]
+    Http(#[from] HttpError),
+    #[error("Failed to receive the file list: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Received an invalid file list: {0}")]
+    Xml(String),
+    #[error("Failed to parse the file list: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum KeyBindingError {
+    #[error("Unknown key \"{0}\"")]
+    UnknownKey(String),
+    #[error("Unknown modifier \"{0}\", expected ctrl, alt or shift")]
+    UnknownModifier(String),
+}
+
+#[derive(Debug, Error)]
+pub enum TextureError {
+    #[error("Failed to decode the PNG: {0}")]
+    Png(#[from] png::DecodingError),
+    #[error("Unsupported pixel format {0}")]
+    UnsupportedFormat(String),
+    #[error("Texture data is truncated")]
+    Truncated,
+}
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("Failed to write the export: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("Failed to serialize the assets: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Failed to write the database: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum DownloadError {
+    #[error("Refusing to save the asset: {0}")]
+    UnsafePath(String),
+    #[error(transparent)]
+    Http(#[from] HttpError),
+    #[error("Failed to write the file: {0}")]
+    IO(#[from] std::io::Error),
+    #[error(
+        "Not enough disk space: {} needed, {} available",
+        crate::bytes_to_human_readable(*.needed as i64),
+        crate::bytes_to_human_readable(*.available as i64)
+    )]
+    InsufficientSpace { needed: u64, available: u64 },
+    #[error("Cancelled")]
+    Cancelled,
+}
+
+#[derive(Debug, Error)]
+pub enum SyncError {
+    #[error("No earlier revision with downloaded assets found")]
+    NoPreviousRevision,
+    #[error("Failed to read the cached file list: {0}")]
+    IO(#[from] std::io::Error),
+    #[error(transparent)]
+    Index(#[from] IndexError),
+}
+
+#[derive(Debug, Error)]
+pub enum StoreError {
+    #[error("Failed to access the object store: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("Invalid manifest: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Revision {0} is not in the object store")]
+    UnknownRevision(String),
+}
+
+#[derive(Debug, Error)]
+pub enum WadError {
+    #[error("Not a KIWAD archive")]
+    NotAWad,
+    #[error("Corrupt archive: {0}")]
+    Corrupt(String),
+    #[error("Failed to read the archive: {0}")]
+    IO(#[from] std::io::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum HookError {
+    #[error("Expected either an action or a command")]
+    InvalidAction,
+    #[error("Invalid glob \"{0}\"")]
+    InvalidGlob(String),
+    #[error("{0}")]
+    IO(#[from] std::io::Error),
+    #[error("File is {0}")]
+    Verification(crate::verify::Verification),
+    #[error(transparent)]
+    Wad(#[from] WadError),
+    #[error("Neither gzip nor zlib compressed")]
+    NotCompressed,
+    #[error("Command failed with {0}: {1}")]
+    Failed(std::process::ExitStatus, String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum UnsafePathError {
+    #[error("Empty filename")]
+    Empty,
+    #[error("Absolute path")]
+    Absolute,
+    #[error("Path starts with a drive letter")]
+    DriveLetter,
+    #[error("Path leaves the assets folder with \"..\"")]
+    Traversal,
+    #[error("Invalid character {0:?}")]
+    InvalidCharacter(char),
+    #[error("\"{0}\" ends with a dot or space")]
+    TrailingDotOrSpace(String),
+    #[error("\"{0}\" is a reserved device name")]
+    ReservedName(String),
+    #[error("Names a folder, not a file")]
+    TrailingSeparator,
+    #[error("Saved to the same file as {0}")]
+    SameFileAs(String),
+}
+
+#[derive(Debug, Error)]
+pub enum HttpError {
+    #[error("Invalid HTTP settings: {0}")]
+    Config(reqwest::Error),
+    #[error("Invalid HTTP header \"{0}\"")]
+    InvalidHeader(String),
+    #[error("Invalid proxy \"{0}\", expected an http://, https:// or socks5:// URL")]
+    InvalidProxy(String),
+    #[error("Request to {url} failed: {source}")]
+    Request { url: String, source: reqwest::Error },
+    #[error("{url} responded with {status}")]
+    Status { url: String, status: reqwest::StatusCode },
+}
@@ -0,0 +1,349 @@
+use crate::{errors::ExportError, parser::parser::Asset, revision_checker::Revision};
+use chrono::Local;
+use clap::ValueEnum;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::{
+    fmt, fs,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Sqlite,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Sqlite => "sqlite",
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportFormat::Json => write!(f, "JSON"),
+            ExportFormat::Csv => write!(f, "CSV"),
+            ExportFormat::Sqlite => write!(f, "SQLite"),
+        }
+    }
+}
+
+/// Exports are written to `exports/` unless another path is given
+pub fn default_path(revision: &str, format: ExportFormat, filtered: bool) -> PathBuf {
+    let suffix = match filtered {
+        true => format!("-filtered-{}", Local::now().format("%Y%m%d-%H%M%S")),
+        false => String::new(),
+    };
+
+    PathBuf::from("exports").join(format!("{revision}{suffix}.{}", format.extension()))
+}
+
+#[derive(Serialize)]
+struct JsonExport<'a> {
+    revision: &'a Revision,
+    exported_at: String,
+    asset_count: usize,
+    assets: &'a [Asset],
+}
+
+/// Writes `assets` together with the metadata of their revision to `path`, replacing an existing file
+pub fn export(assets: &[Asset], revision: &Revision, format: ExportFormat, path: &Path) -> Result<(), ExportError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    match format {
+        ExportFormat::Json => export_json(assets, revision, path),
+        ExportFormat::Csv => export_csv(assets, revision, path),
+        ExportFormat::Sqlite => export_sqlite(assets, revision, path),
+    }
+}
+
+fn export_json(assets: &[Asset], revision: &Revision, path: &Path) -> Result<(), ExportError> {
+    let export = JsonExport {
+        revision,
+        exported_at: Local::now().to_rfc3339(),
+        asset_count: assets.len(),
+        assets,
+    };
+
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, &export)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// One row per asset, the revision is repeated in every row so spreadsheets can combine several exports
+fn export_csv(assets: &[Asset], revision: &Revision, path: &Path) -> Result<(), ExportError> {
+    const HEADER: [&str; 12] = [
+        "filename",
+        "size",
+        "header_size",
+        "compressed_header_size",
+        "crc",
+        "header_crc",
+        "already_fetched",
+        "table",
+        "rejected",
+        "revision",
+        "url",
+        "list_file_url",
+    ];
+
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    writeln!(writer, "{}", HEADER.join(","))?;
+
+    for asset in assets {
+        let row = [
+            asset.filename.clone(),
+            asset.size.to_string(),
+            asset.header_size.to_string(),
+            asset.compressed_header_size.to_string(),
+            asset.crc.to_string(),
+            asset.header_crc.to_string(),
+            asset.already_fetched.to_string(),
+            asset.table.clone(),
+            asset.rejected.clone().unwrap_or_default(),
+            revision.revision.clone(),
+            format!("{}/{}", revision.url_prefix, asset.filename),
+            revision.list_file_url.clone(),
+        ];
+
+        let row: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        writeln!(writer, "{}", row.join(","))?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Quotes a field if it contains a separator, a quote or a line break
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+fn export_sqlite(assets: &[Asset], revision: &Revision, path: &Path) -> Result<(), ExportError> {
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+
+    let mut connection = Connection::open(path)?;
+    connection.execute_batch(
+        "CREATE TABLE revision (
+            revision TEXT NOT NULL,
+            url_prefix TEXT NOT NULL,
+            list_file_url TEXT NOT NULL,
+            exported_at TEXT NOT NULL
+        );
+        CREATE TABLE assets (
+            filename TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            header_size INTEGER NOT NULL,
+            compressed_header_size INTEGER NOT NULL,
+            crc INTEGER NOT NULL,
+            header_crc INTEGER NOT NULL,
+            already_fetched INTEGER NOT NULL,
+            \"table\" TEXT NOT NULL,
+            rejected TEXT,
+            url TEXT NOT NULL
+        );
+        CREATE INDEX assets_filename ON assets (filename);",
+    )?;
+
+    let transaction = connection.transaction()?;
+    transaction.execute(
+        "INSERT INTO revision VALUES (?1, ?2, ?3, ?4)",
+        params![
+            revision.revision,
+            revision.url_prefix,
+            revision.list_file_url,
+            Local::now().to_rfc3339()
+        ],
+    )?;
+
+    {
+        let mut insert = transaction.prepare("INSERT INTO assets VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)")?;
+        for asset in assets {
+            insert.execute(params![
+                asset.filename,
+                asset.size,
+                asset.header_size,
+                asset.compressed_header_size,
+                asset.crc,
+                asset.header_crc,
+                asset.already_fetched,
+                asset.table,
+                asset.rejected,
+                format!("{}/{}", revision.url_prefix, asset.filename),
+            ])?;
+        }
+    }
+
+    transaction.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn revision() -> Revision {
+        Revision {
+            list_file_url: "http://patch.example/LatestFileList.bin".to_string(),
+            url_prefix: "http://patch.example/files".to_string(),
+            revision: "V_r1.0".to_string(),
+        }
+    }
+
+    fn assets() -> Vec<Asset> {
+        vec![
+            Asset {
+                filename: "Data/GameData/Root.wad".to_string(),
+                size: 1024,
+                header_size: 12,
+                compressed_header_size: 8,
+                crc: 305419896,
+                header_crc: -1,
+                already_fetched: true,
+                table: "_Shared".to_string(),
+                rejected: None,
+            },
+            Asset {
+                filename: "../\"odd\", name\nwith breaks.txt".to_string(),
+                size: 1,
+                table: "_Shared".to_string(),
+                rejected: Some("Path leaves the assets folder with \"..\"".to_string()),
+                ..Default::default()
+            },
+        ]
+    }
+
+    /// Splits CSV into rows of fields, undoing the quoting of `csv_field`
+    fn parse_csv(text: &str) -> Vec<Vec<String>> {
+        let (mut rows, mut row, mut field) = (Vec::new(), Vec::new(), String::new());
+        let mut chars = text.chars().peekable();
+        let mut quoted = false;
+
+        while let Some(c) = chars.next() {
+            match (c, quoted) {
+                ('"', true) if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                ('"', _) => quoted = !quoted,
+                (',', false) => row.push(std::mem::take(&mut field)),
+                ('\n', false) => {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                (c, _) => field.push(c),
+            }
+        }
+        rows
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        assert_eq!(csv_field("Data/Root.wad"), "Data/Root.wad");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("carriage\rreturn"), "\"carriage\rreturn\"");
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/export.csv");
+        export(&assets(), &revision(), ExportFormat::Csv, &path).unwrap();
+
+        let rows = parse_csv(&fs::read_to_string(&path).unwrap());
+        assert_eq!(rows.len(), 3);
+        assert!(rows.iter().all(|row| row.len() == rows[0].len()));
+
+        let column = |row: &[String], name: &str| row[rows[0].iter().position(|column| column == name).unwrap()].clone();
+        for (row, asset) in rows[1..].iter().zip(assets()) {
+            assert_eq!(column(row, "filename"), asset.filename);
+            assert_eq!(column(row, "size"), asset.size.to_string());
+            assert_eq!(column(row, "header_size"), asset.header_size.to_string());
+            assert_eq!(column(row, "compressed_header_size"), asset.compressed_header_size.to_string());
+            assert_eq!(column(row, "crc"), asset.crc.to_string());
+            assert_eq!(column(row, "header_crc"), asset.header_crc.to_string());
+            assert_eq!(column(row, "already_fetched"), asset.already_fetched.to_string());
+            assert_eq!(column(row, "table"), asset.table);
+            assert_eq!(column(row, "rejected"), asset.rejected.unwrap_or_default());
+            assert_eq!(column(row, "revision"), "V_r1.0");
+            assert_eq!(column(row, "url"), format!("http://patch.example/files/{}", asset.filename));
+        }
+    }
+
+    #[test]
+    fn sqlite_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.sqlite");
+        // An existing export is replaced, not appended to
+        export(&assets(), &revision(), ExportFormat::Sqlite, &path).unwrap();
+        export(&assets(), &revision(), ExportFormat::Sqlite, &path).unwrap();
+
+        let connection = Connection::open(&path).unwrap();
+        let mut select = connection
+            .prepare(
+                "SELECT filename, size, header_size, compressed_header_size, crc, header_crc, already_fetched, \"table\", rejected, url
+                FROM assets ORDER BY rowid",
+            )
+            .unwrap();
+        let rows: Vec<(Asset, String)> = select
+            .query_map([], |row| {
+                let asset = Asset {
+                    filename: row.get(0)?,
+                    size: row.get(1)?,
+                    header_size: row.get(2)?,
+                    compressed_header_size: row.get(3)?,
+                    crc: row.get(4)?,
+                    header_crc: row.get(5)?,
+                    already_fetched: row.get(6)?,
+                    table: row.get(7)?,
+                    rejected: row.get(8)?,
+                };
+                Ok((asset, row.get(9)?))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let expected: Vec<Asset> = assets();
+        assert_eq!(rows.iter().map(|(asset, _)| asset.clone()).collect::<Vec<_>>(), expected);
+        assert_eq!(rows[0].1, "http://patch.example/files/Data/GameData/Root.wad");
+
+        let revision: (String, String) = connection
+            .query_row("SELECT revision, url_prefix FROM revision", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(revision, ("V_r1.0".to_string(), "http://patch.example/files".to_string()));
+    }
+
+    #[test]
+    fn json_keeps_the_rejection() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.json");
+        export(&assets(), &revision(), ExportFormat::Json, &path).unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["asset_count"], 2);
+        assert_eq!(json["assets"][0].get("rejected"), None);
+        assert_eq!(json["assets"][1]["rejected"], "Path leaves the assets folder with \"..\"");
+    }
+}
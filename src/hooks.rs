@@ -0,0 +1,371 @@
+use crate::{
+    errors::HookError,
+    parser::parser::Asset,
+    settings::{BuiltinAction, HookSettings},
+    util::shell_command,
+    verify::verify,
+    wad,
+};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use regex::{Regex, RegexBuilder};
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+/// Post-download hooks from the settings, run one after another on every downloaded asset they match
+#[derive(Debug, Clone, Default)]
+pub struct Hooks {
+    hooks: Vec<Hook>,
+}
+
+#[derive(Debug, Clone)]
+struct Hook {
+    glob: Option<Regex>,
+    extension: Option<String>,
+    run: Run,
+}
+
+#[derive(Debug, Clone)]
+enum Run {
+    Builtin(BuiltinAction),
+    Command(String),
+}
+
+impl Hooks {
+    /// Invalid hooks are skipped and logged
+    pub fn new(settings: &[HookSettings]) -> Self {
+        let hooks = settings
+            .iter()
+            .enumerate()
+            .filter_map(|(i, settings)| match Hook::new(settings) {
+                Ok(hook) => Some(hook),
+                Err(e) => {
+                    log::error!("Skipped hook {}: {e}", i + 1);
+                    None
+                }
+            })
+            .collect();
+
+        Self { hooks }
+    }
+
+    /// Runs every hook matching `asset`, which was saved to `path`, and logs the results. Returns whether any hook ran.
+    /// A failing hook does not stop the following ones.
+    pub async fn run(&self, path: &Path, asset: &Asset, revision: &str) -> bool {
+        let mut ran = false;
+        for hook in self.hooks.iter().filter(|hook| hook.matches(asset)) {
+            match hook.run(path, asset, revision).await {
+                Ok(result) => log::info!("Hook {} on {}: {result}", hook.run, asset.filename),
+                Err(e) => log::warn!("Hook {} on {} failed: {e}", hook.run, asset.filename),
+            }
+            ran = true;
+        }
+        ran
+    }
+}
+
+impl Hook {
+    fn new(settings: &HookSettings) -> Result<Self, HookError> {
+        let run = match (settings.action, &settings.command) {
+            (Some(action), None) => Run::Builtin(action),
+            (None, Some(command)) => Run::Command(command.clone()),
+            _ => return Err(HookError::InvalidAction),
+        };
+
+        Ok(Self {
+            glob: settings.glob.as_deref().map(glob_to_regex).transpose()?,
+            extension: settings
+                .extension
+                .as_ref()
+                .map(|extension| extension.trim_start_matches('.').to_string()),
+            run,
+        })
+    }
+
+    /// Both the glob and the extension have to match, if given
+    fn matches(&self, asset: &Asset) -> bool {
+        let glob = self.glob.as_ref().is_none_or(|glob| glob.is_match(&asset.filename));
+        let extension = self.extension.as_ref().is_none_or(|extension| {
+            Path::new(&asset.filename)
+                .extension()
+                .is_some_and(|actual| actual.to_string_lossy().eq_ignore_ascii_case(extension))
+        });
+
+        glob && extension
+    }
+
+    async fn run(&self, path: &Path, asset: &Asset, revision: &str) -> Result<String, HookError> {
+        let (path, asset) = (path.to_path_buf(), asset.clone());
+
+        match &self.run {
+            Run::Builtin(action) => {
+                let action = *action;
+                tokio::task::spawn_blocking(move || run_builtin(action, &path, &asset))
+                    .await
+                    .map_err(io::Error::other)?
+            }
+            Run::Command(command) => run_command(command, &path, &asset, revision).await,
+        }
+    }
+}
+
+impl std::fmt::Display for Run {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Run::Builtin(action) => write!(f, "{action}"),
+            Run::Command(command) => write!(f, "`{command}`"),
+        }
+    }
+}
+
+/// `*` and `?` match within a directory, `**` across directories. Matching ignores case, like the game's file system.
+fn glob_to_regex(glob: &str) -> Result<Regex, HookError> {
+    let mut pattern = String::from("^");
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                match chars.peek() == Some(&'/') {
+                    true => {
+                        chars.next();
+                        pattern.push_str("(?:.*/)?");
+                    }
+                    false => pattern.push_str(".*"),
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern.push('$');
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|_| HookError::InvalidGlob(glob.to_string()))
+}
+
+fn run_builtin(action: BuiltinAction, path: &Path, asset: &Asset) -> Result<String, HookError> {
+    match action {
+        BuiltinAction::Verify => match verify(path, asset)? {
+            verification if verification.is_verified() => Ok(verification.to_string()),
+            verification => Err(HookError::Verification(verification)),
+        },
+        BuiltinAction::ExtractWad => {
+            let dir = path.with_extension("");
+            let (extracted, skipped) = wad::extract(path, &dir)?;
+            Ok(format!("extracted {extracted} files to {} ({skipped} skipped)", dir.display()))
+        }
+        BuiltinAction::Decompress => {
+            let target = decompressed_path(path);
+            let size = decompress(path, &target)?;
+            Ok(format!("decompressed {size} bytes to {}", target.display()))
+        }
+    }
+}
+
+/// Strips a compression extension, or appends `.decompressed` if there is none
+fn decompressed_path(path: &Path) -> PathBuf {
+    let compressed = path
+        .extension()
+        .is_some_and(|extension| ["gz", "z", "zlib"].iter().any(|known| extension.eq_ignore_ascii_case(known)));
+
+    match compressed {
+        true => path.with_extension(""),
+        false => PathBuf::from(format!("{}.decompressed", path.display())),
+    }
+}
+
+/// Decompresses a gzip or zlib stream, detected from its header
+fn decompress(path: &Path, target: &Path) -> Result<usize, HookError> {
+    let data = fs::read(path)?;
+    let mut content = Vec::new();
+
+    match data.as_slice() {
+        [0x1f, 0x8b, ..] => GzDecoder::new(data.as_slice()).read_to_end(&mut content)?,
+        [cmf, flg, ..] if cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0 => {
+            ZlibDecoder::new(data.as_slice()).read_to_end(&mut content)?
+        }
+        _ => return Err(HookError::NotCompressed),
+    };
+
+    fs::write(target, &content)?;
+    Ok(content.len())
+}
+
+/// Runs `command` through the shell with the asset passed in environment variables, failing if it exits unsuccessfully
+async fn run_command(command: &str, path: &Path, asset: &Asset, revision: &str) -> Result<String, HookError> {
+    let output = shell_command(command)
+        .env("ARCANE_ASSET_PATH", path)
+        .env("ARCANE_ASSET_FILENAME", &asset.filename)
+        .env("ARCANE_ASSET_SIZE", asset.size.to_string())
+        .env("ARCANE_ASSET_CRC", asset.crc.to_string())
+        .env("ARCANE_ASSET_TABLE", &asset.table)
+        .env("ARCANE_REVISION", revision)
+        .stdin(Stdio::null())
+        .output()
+        .await?;
+
+    // The last line is usually the most telling one, e.g. a summary or the error
+    let last_line = |bytes: &[u8]| String::from_utf8_lossy(bytes).lines().last().unwrap_or_default().trim().to_string();
+
+    match output.status.success() {
+        true => Ok(match last_line(&output.stdout) {
+            line if line.is_empty() => "done".to_string(),
+            line => line,
+        }),
+        false => Err(HookError::Failed(
+            output.status,
+            match last_line(&output.stderr) {
+                line if line.is_empty() => "no output".to_string(),
+                line => line,
+            },
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{
+        write::{GzEncoder, ZlibEncoder},
+        Compression,
+    };
+    use std::io::Write;
+
+    fn glob(glob: &str) -> Regex {
+        glob_to_regex(glob).expect(glob)
+    }
+
+    fn asset(filename: &str) -> Asset {
+        Asset {
+            filename: filename.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn single_stars_stay_within_a_directory() {
+        let wads = glob("Data/*.wad");
+        assert!(wads.is_match("Data/Root.wad"));
+        assert!(wads.is_match("Data/.wad"));
+        assert!(!wads.is_match("Data/GameData/Root.wad"));
+        assert!(!wads.is_match("Bin/Data/Root.wad"));
+        assert!(!wads.is_match("Data/Root.wad.bak"));
+
+        let single = glob("Data/Root?.wad");
+        assert!(single.is_match("Data/Root1.wad"));
+        assert!(!single.is_match("Data/Root.wad"));
+        assert!(!single.is_match("Data/Root12.wad"));
+        assert!(!glob("Data?Root.wad").is_match("Data/Root.wad"));
+    }
+
+    #[test]
+    fn double_stars_cross_directories() {
+        let anywhere = glob("**/*.xml");
+        assert!(anywhere.is_match("console.xml"));
+        assert!(anywhere.is_match("Data/GameData/console.xml"));
+        assert!(!anywhere.is_match("Data/console.xml.gz"));
+
+        let below = glob("Data/**/Root.wad");
+        assert!(below.is_match("Data/Root.wad"));
+        assert!(below.is_match("Data/GameData/Deep/Root.wad"));
+        assert!(!below.is_match("Bin/Root.wad"));
+
+        let suffix = glob("Data/**");
+        assert!(suffix.is_match("Data/GameData/Root.wad"));
+        assert!(!suffix.is_match("Bin/Data/Root.wad"));
+    }
+
+    #[test]
+    fn globs_ignore_case_and_escape_regex_syntax() {
+        assert!(glob("data/*.WAD").is_match("Data/Root.wad"));
+        assert!(glob("Bin/(1)+[x].txt").is_match("Bin/(1)+[x].txt"));
+        assert!(!glob("Bin/a.txt").is_match("Bin/abtxt"));
+        assert!(!glob("Bin/a.txt").is_match("Bin/a.txt/more"));
+    }
+
+    #[test]
+    fn matches_glob_and_extension() {
+        let hook = |glob: Option<&str>, extension: Option<&str>| {
+            Hook::new(&HookSettings {
+                glob: glob.map(str::to_string),
+                extension: extension.map(str::to_string),
+                action: Some(BuiltinAction::Verify),
+                command: None,
+            })
+            .unwrap()
+        };
+
+        assert!(hook(None, None).matches(&asset("anything")));
+        assert!(hook(None, Some(".WAD")).matches(&asset("Data/Root.wad")));
+        assert!(!hook(None, Some("wad")).matches(&asset("Data/wad")));
+        let both = hook(Some("Data/*"), Some("wad"));
+        assert!(both.matches(&asset("Data/Root.wad")));
+        assert!(!both.matches(&asset("Data/Root.xml")));
+        assert!(!both.matches(&asset("Bin/Root.wad")));
+    }
+
+    #[test]
+    fn needs_either_an_action_or_a_command() {
+        let settings = |action: Option<BuiltinAction>, command: Option<&str>| HookSettings {
+            glob: None,
+            extension: None,
+            action,
+            command: command.map(str::to_string),
+        };
+
+        assert!(matches!(Hook::new(&settings(None, None)), Err(HookError::InvalidAction)));
+        assert!(matches!(
+            Hook::new(&settings(Some(BuiltinAction::Verify), Some("true"))),
+            Err(HookError::InvalidAction)
+        ));
+        assert!(Hook::new(&settings(None, Some("true"))).is_ok());
+    }
+
+    #[test]
+    fn names_decompressed_files() {
+        assert_eq!(decompressed_path(Path::new("Data/notes.txt.gz")), Path::new("Data/notes.txt"));
+        assert_eq!(decompressed_path(Path::new("Data/blob.ZLIB")), Path::new("Data/blob"));
+        assert_eq!(
+            decompressed_path(Path::new("Data/blob.bin")),
+            Path::new("Data/blob.bin.decompressed")
+        );
+        assert_eq!(decompressed_path(Path::new("Data/blob")), Path::new("Data/blob.decompressed"));
+    }
+
+    #[test]
+    fn detects_the_compression_from_the_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, target) = (dir.path().join("source"), dir.path().join("target"));
+        let content = b"<Root>compressed content</Root>".repeat(10);
+
+        let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+        gzip.write_all(&content).unwrap();
+        fs::write(&source, gzip.finish().unwrap()).unwrap();
+        assert_eq!(decompress(&source, &target).unwrap(), content.len());
+        assert_eq!(fs::read(&target).unwrap(), content);
+
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::best());
+        zlib.write_all(&content).unwrap();
+        let zlib = zlib.finish().unwrap();
+        fs::write(&source, &zlib).unwrap();
+        assert_eq!(decompress(&source, &target).unwrap(), content.len());
+        assert_eq!(fs::read(&target).unwrap(), content);
+
+        // A zlib stream cut off in the middle is an error, not a shorter file
+        fs::write(&source, &zlib[..zlib.len() / 2]).unwrap();
+        assert!(matches!(decompress(&source, &target), Err(HookError::IO(_))));
+
+        for plain in [&b"plain text"[..], b"x", b"", b"\x78\x00 not a valid zlib header check"] {
+            fs::write(&source, plain).unwrap();
+            assert!(matches!(decompress(&source, &target), Err(HookError::NotCompressed)), "{plain:?}");
+        }
+    }
+}
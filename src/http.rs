@@ -0,0 +1,132 @@
+use crate::{errors::HttpError, settings::HttpSettings};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, RANGE, USER_AGENT},
+    Client, Proxy, RequestBuilder, Response,
+};
+use std::{sync::OnceLock, time::Duration};
+
+/// Longest wait between two attempts, however often a request was retried
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+const PROXY_SCHEMES: [&str; 4] = ["http", "https", "socks5", "socks5h"];
+
+static CLIENT: OnceLock<HttpClient> = OnceLock::new();
+
+/// The connection pool every request goes through, configured from the `http` settings
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: Client,
+    retries: u32,
+    backoff: Duration,
+}
+
+/// Configures the shared client. Has to be called before the first request, later calls are ignored.
+pub fn init(settings: &HttpSettings) -> Result<(), HttpError> {
+    let client = HttpClient::new(settings)?;
+    let _ = CLIENT.set(client);
+    Ok(())
+}
+
+/// The shared client, with the default settings if `init` was not called
+pub fn client() -> &'static HttpClient {
+    CLIENT.get_or_init(|| HttpClient::new(&HttpSettings::default()).expect("the default HTTP settings are valid"))
+}
+
+impl HttpClient {
+    fn new(settings: &HttpSettings) -> Result<Self, HttpError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &settings.headers {
+            let name = HeaderName::try_from(name).map_err(|_| HttpError::InvalidHeader(name.clone()))?;
+            let value = HeaderValue::try_from(value).map_err(|_| HttpError::InvalidHeader(name.to_string()))?;
+            headers.insert(name, value);
+        }
+
+        let mut builder = Client::builder()
+            .user_agent(&settings.user_agent)
+            .default_headers(headers)
+            .connect_timeout(Duration::from_secs(settings.connect_timeout_secs))
+            .read_timeout(Duration::from_secs(settings.read_timeout_secs));
+        if let Some(proxy) = &settings.proxy {
+            if !PROXY_SCHEMES.iter().any(|scheme| proxy.starts_with(&format!("{scheme}://"))) {
+                return Err(HttpError::InvalidProxy(proxy.clone()));
+            }
+            builder = builder.proxy(Proxy::all(proxy).map_err(HttpError::Config)?);
+        }
+
+        Ok(Self {
+            client: builder.build().map_err(HttpError::Config)?,
+            retries: settings.retries,
+            backoff: Duration::from_millis(settings.retry_delay_ms),
+        })
+    }
+
+    /// GETs `url`, failing on any status other than success
+    pub async fn get(&self, url: &str) -> Result<Response, HttpError> {
+        self.send(url, || self.client.get(url)).await
+    }
+
+    /// GETs `url` from byte `offset` on. Servers which do not support ranges send the whole file instead,
+    /// only `206 Partial Content` starts at the offset.
+    pub async fn get_from(&self, url: &str, offset: u64) -> Result<Response, HttpError> {
+        self.send(url, || self.client.get(url).header(RANGE, format!("bytes={offset}-")))
+            .await
+    }
+
+    pub async fn post_json(&self, url: &str, json: String, user_agent: &str) -> Result<Response, HttpError> {
+        self.send(url, || {
+            self.client
+                .post(url)
+                .header(CONTENT_TYPE, "application/json")
+                .header(USER_AGENT, user_agent)
+                .body(json.clone())
+        })
+        .await
+    }
+
+    /// How long to wait before the retry following `attempt` earlier ones, `None` once every retry is used up
+    pub fn retry_delay(&self, attempt: u32) -> Option<Duration> {
+        (attempt < self.retries).then(|| self.backoff.saturating_mul(2u32.saturating_pow(attempt)).min(MAX_BACKOFF))
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// Sends the request built by `request`, retrying with an exponentially growing delay
+    /// after server errors, timeouts and failed connections
+    async fn send(&self, url: &str, request: impl Fn() -> RequestBuilder) -> Result<Response, HttpError> {
+        let mut attempt = 0;
+
+        loop {
+            let error = match request().send().await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => HttpError::Status {
+                    url: url.to_string(),
+                    status: response.status(),
+                },
+                Err(source) => HttpError::Request {
+                    url: url.to_string(),
+                    source,
+                },
+            };
+
+            let Some(delay) = self.retry_delay(attempt).filter(|_| error.is_transient()) else {
+                return Err(error);
+            };
+            attempt += 1;
+            log::warn!("{error}, retrying in {:.1}s ({attempt}/{})", delay.as_secs_f64(), self.retries);
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+impl HttpError {
+    /// Whether trying again later may succeed. Besides failed requests, this includes a body cut off while it was received.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            HttpError::Status { status, .. } => status.is_server_error(),
+            HttpError::Request { source, .. } => source.is_connect() || source.is_timeout() || source.is_body() || source.is_decode(),
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use crate::{revision_checker::Revision, util::data_dir};
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const REVISION_FILE: &str = "revision.json";
+const INDEX_FILE: &str = "LatestFileList.xml";
+
+/// Every downloaded file list is kept in `data/index/<revision>/`, so Arcane can start without a connection to the patch server
+fn index_dir() -> PathBuf {
+    data_dir().join("index")
+}
+
+pub fn save(revision: &Revision, xml: &str) -> io::Result<()> {
+    let dir = index_dir().join(&revision.revision);
+    fs::create_dir_all(&dir)?;
+
+    fs::write(dir.join(REVISION_FILE), serde_json::to_string_pretty(revision)?)?;
+    fs::write(dir.join(INDEX_FILE), xml)
+}
+
+pub fn load(revision: &str) -> io::Result<(Revision, String)> {
+    let dir = index_dir().join(revision);
+    let revision: Revision = serde_json::from_str(&fs::read_to_string(dir.join(REVISION_FILE))?)?;
+    let xml = fs::read_to_string(dir.join(INDEX_FILE))?;
+
+    Ok((revision, xml))
+}
+
+/// All cached revisions, the most recently saved one last
+pub fn revisions() -> io::Result<Vec<String>> {
+    let dir = index_dir();
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut revisions = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let Ok(metadata) = fs::metadata(entry.path().join(REVISION_FILE)) else {
+            continue;
+        };
+
+        let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+        revisions.push((modified, entry.file_name().to_string_lossy().to_string()));
+    }
+
+    revisions.sort();
+    Ok(revisions.into_iter().map(|(_, revision)| revision).collect())
+}
+
+/// The most recently saved revision
+pub fn latest() -> io::Result<Option<String>> {
+    Ok(revisions()?.pop())
+}
+
+/// Age of the cached file list of `revision`
+pub fn age(revision: &str) -> Option<std::time::Duration> {
+    let modified = fs::metadata(index_dir().join(revision).join(REVISION_FILE)).ok()?.modified().ok()?;
+    SystemTime::now().duration_since(modified).ok()
+}
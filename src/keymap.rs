@@ -0,0 +1,477 @@
+use crate::{
+    errors::KeyBindingError,
+    settings::{KeySettings, Preset},
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+use tui_textarea::{Input, Key};
+
+/// Everything that can be bound to a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Fetch,
+    ToggleDetails,
+    Mark,
+    MarkAll,
+    ClearMarks,
+    FetchMarked,
+    FocusSearch,
+    FocusList,
+    ToggleView,
+    Expand,
+    Collapse,
+    ToggleLogs,
+    ScrollLogsUp,
+    ScrollLogsDown,
+    CycleSort,
+    ReverseSort,
+    Export,
+    Mirror,
+    Sync,
+    PauseDownload,
+    CancelDownload,
+    QueueEarlier,
+    QueueLater,
+    LowerRateLimit,
+    RaiseRateLimit,
+    Help,
+}
+
+impl Action {
+    pub const ALL: [Action; 33] = [
+        Action::Quit,
+        Action::Up,
+        Action::Down,
+        Action::PageUp,
+        Action::PageDown,
+        Action::Home,
+        Action::End,
+        Action::Fetch,
+        Action::ToggleDetails,
+        Action::Mark,
+        Action::MarkAll,
+        Action::ClearMarks,
+        Action::FetchMarked,
+        Action::FocusSearch,
+        Action::FocusList,
+        Action::ToggleView,
+        Action::Expand,
+        Action::Collapse,
+        Action::ToggleLogs,
+        Action::ScrollLogsUp,
+        Action::ScrollLogsDown,
+        Action::CycleSort,
+        Action::ReverseSort,
+        Action::Export,
+        Action::Mirror,
+        Action::Sync,
+        Action::PauseDownload,
+        Action::CancelDownload,
+        Action::QueueEarlier,
+        Action::QueueLater,
+        Action::LowerRateLimit,
+        Action::RaiseRateLimit,
+        Action::Help,
+    ];
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::Up => "Select the previous asset",
+            Action::Down => "Select the next asset",
+            Action::PageUp => "Move one page up",
+            Action::PageDown => "Move one page down",
+            Action::Home => "Select the first asset",
+            Action::End => "Select the last asset",
+            Action::Fetch => "Fetch the selected asset or directory",
+            Action::ToggleDetails => "Toggle the details pane",
+            Action::Mark => "(Un-)mark the selected asset",
+            Action::MarkAll => "(Un-)mark all search results",
+            Action::ClearMarks => "Clear all marks",
+            Action::FetchMarked => "Fetch all marked assets",
+            Action::FocusSearch => "Focus the search box",
+            Action::FocusList => "Focus the asset list",
+            Action::ToggleView => "Switch between list and tree view",
+            Action::Expand => "Expand the selected directory",
+            Action::Collapse => "Collapse the selected directory",
+            Action::ToggleLogs => "Toggle the log pane",
+            Action::ScrollLogsUp => "Scroll the log pane up",
+            Action::ScrollLogsDown => "Scroll the log pane down",
+            Action::CycleSort => "Cycle the sort order",
+            Action::ReverseSort => "Reverse the sort order",
+            Action::Export => "Export the search results",
+            Action::Mirror => "Mirror every asset of the revision",
+            Action::Sync => "Mirror, reusing unchanged files of an earlier revision",
+            Action::PauseDownload => "Pause or resume the selected queued download",
+            Action::CancelDownload => "Cancel the selected download, queued or running",
+            Action::QueueEarlier => "Move the selected download up in the queue",
+            Action::QueueLater => "Move the selected download down in the queue",
+            Action::LowerRateLimit => "Lower the download speed limit",
+            Action::RaiseRateLimit => "Raise the download speed limit",
+            Action::Help => "Show this help",
+        }
+    }
+}
+
+/// A key combination like `ctrl+a` or `pageup`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyBinding {
+    key: Key,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+}
+
+impl KeyBinding {
+    pub fn matches(&self, input: &Input) -> bool {
+        // Shift is already part of the character itself (`?` vs `/`)
+        let shift_matches = matches!(self.key, Key::Char(_)) || self.shift == input.shift;
+        self.key == input.key && self.ctrl == input.ctrl && self.alt == input.alt && shift_matches
+    }
+
+    /// Plain characters, including space, are typed into the search box while it is focused
+    pub fn is_text(input: &Input) -> bool {
+        matches!(input.key, Key::Char(_)) && !input.ctrl && !input.alt
+    }
+
+    /// Keys the focused search box handles itself: text, and moving the cursor within it
+    pub fn edits_text(input: &Input) -> bool {
+        let moves_cursor = matches!(input.key, Key::Left | Key::Right | Key::Home | Key::End) && !input.ctrl && !input.alt && !input.shift;
+        Self::is_text(input) || moves_cursor
+    }
+}
+
+impl FromStr for KeyBinding {
+    type Err = KeyBindingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut binding = KeyBinding {
+            key: Key::Null,
+            ctrl: false,
+            alt: false,
+            shift: false,
+        };
+
+        let lowercase = s.to_ascii_lowercase();
+        let mut parts: Vec<&str> = lowercase.split('+').collect();
+        // `ctrl++` binds the plus key itself
+        if lowercase.ends_with("++") || lowercase == "+" {
+            parts.retain(|part| !part.is_empty());
+            parts.push("+");
+        }
+
+        let Some((key, modifiers)) = parts.split_last() else {
+            return Err(KeyBindingError::UnknownKey(s.to_string()));
+        };
+
+        for modifier in modifiers {
+            match *modifier {
+                "ctrl" => binding.ctrl = true,
+                "alt" => binding.alt = true,
+                "shift" => binding.shift = true,
+                _ => return Err(KeyBindingError::UnknownModifier(modifier.to_string())),
+            }
+        }
+
+        binding.key = match *key {
+            "enter" => Key::Enter,
+            "esc" => Key::Esc,
+            "tab" => Key::Tab,
+            "space" => Key::Char(' '),
+            "backspace" => Key::Backspace,
+            "delete" => Key::Delete,
+            "up" => Key::Up,
+            "down" => Key::Down,
+            "left" => Key::Left,
+            "right" => Key::Right,
+            "home" => Key::Home,
+            "end" => Key::End,
+            "pageup" => Key::PageUp,
+            "pagedown" => Key::PageDown,
+            f if f.len() > 1 && f.starts_with('f') => match f[1..].parse() {
+                Ok(n) => Key::F(n),
+                Err(_) => return Err(KeyBindingError::UnknownKey(s.to_string())),
+            },
+            c if c.chars().count() == 1 => {
+                // Keep the case of the original character, so `G` and `g` can be bound separately
+                let original = s.chars().last().unwrap_or_default();
+                Key::Char(original)
+            }
+            _ => return Err(KeyBindingError::UnknownKey(s.to_string())),
+        };
+
+        Ok(binding)
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+
+        match self.key {
+            Key::Char(' ') => write!(f, "Space"),
+            Key::Char(c) if self.ctrl || self.alt => write!(f, "{}", c.to_ascii_uppercase()),
+            Key::Char(c) => write!(f, "{c}"),
+            Key::F(n) => write!(f, "F{n}"),
+            Key::PageUp => write!(f, "PageUp"),
+            Key::PageDown => write!(f, "PageDown"),
+            key => write!(f, "{key:?}"),
+        }
+    }
+}
+
+/// Maps key presses to actions, built from a preset and the user's overrides
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    bindings: Vec<(KeyBinding, Action)>,
+}
+
+impl KeyMap {
+    pub fn new(settings: &KeySettings) -> Self {
+        let mut keymap = Self::preset(settings.preset);
+        let mut overrides = Vec::new();
+
+        for (action, keys) in &settings.bindings {
+            keymap.bindings.retain(|(_, a)| a != action);
+
+            for key in keys {
+                match key.parse() {
+                    Ok(binding) => overrides.push((binding, *action)),
+                    Err(e) => log::warn!("Ignoring key binding \"{key}\" for {action:?}: {e}"),
+                }
+            }
+        }
+
+        // The user's keys win over the ones the preset binds to other actions
+        overrides.append(&mut keymap.bindings);
+        Self { bindings: overrides }
+    }
+
+    pub fn preset(preset: Preset) -> Self {
+        let common: &[(&str, Action)] = &[
+            ("up", Action::Up),
+            ("down", Action::Down),
+            ("pageup", Action::PageUp),
+            ("pagedown", Action::PageDown),
+            ("home", Action::Home),
+            ("end", Action::End),
+            ("enter", Action::Fetch),
+            ("tab", Action::Mark),
+            ("ctrl+a", Action::MarkAll),
+            ("ctrl+l", Action::ClearMarks),
+            ("ctrl+d", Action::FetchMarked),
+            ("ctrl+t", Action::ToggleView),
+            ("right", Action::Expand),
+            ("left", Action::Collapse),
+            ("ctrl+o", Action::ToggleLogs),
+            ("shift+pageup", Action::ScrollLogsUp),
+            ("shift+pagedown", Action::ScrollLogsDown),
+            ("ctrl+s", Action::CycleSort),
+            ("ctrl+r", Action::ReverseSort),
+            ("ctrl+e", Action::Export),
+            ("f5", Action::Mirror),
+            ("f6", Action::Sync),
+            ("ctrl+p", Action::PauseDownload),
+            ("ctrl+x", Action::CancelDownload),
+            ("alt+up", Action::QueueEarlier),
+            ("alt+down", Action::QueueLater),
+            ("f7", Action::LowerRateLimit),
+            ("f8", Action::RaiseRateLimit),
+            ("f1", Action::Help),
+        ];
+
+        let specific: &[(&str, Action)] = match preset {
+            Preset::Default => &[
+                ("esc", Action::Quit),
+                ("space", Action::ToggleDetails),
+                ("ctrl+f", Action::FocusSearch),
+                ("ctrl+g", Action::FocusList),
+            ],
+            Preset::Vim => &[
+                ("q", Action::Quit),
+                ("ctrl+c", Action::Quit),
+                ("k", Action::Up),
+                ("j", Action::Down),
+                ("ctrl+u", Action::PageUp),
+                ("ctrl+b", Action::PageUp),
+                ("ctrl+f", Action::PageDown),
+                ("g", Action::Home),
+                ("G", Action::End),
+                ("l", Action::Expand),
+                ("h", Action::Collapse),
+                ("space", Action::ToggleDetails),
+                ("m", Action::Mark),
+                ("/", Action::FocusSearch),
+                ("esc", Action::FocusList),
+                ("?", Action::Help),
+            ],
+        };
+
+        let bindings = common
+            .iter()
+            .chain(specific)
+            .map(|(key, action)| (key.parse().expect("preset key bindings are valid"), *action))
+            .collect();
+
+        Self { bindings }
+    }
+
+    pub fn action(&self, input: &Input) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(binding, _)| binding.matches(input))
+            .map(|(_, action)| *action)
+    }
+
+    /// All keys bound to `action`, for the help overlay
+    pub fn keys(&self, action: Action) -> Vec<KeyBinding> {
+        self.bindings
+            .iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(binding, _)| *binding)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn binding(s: &str) -> KeyBinding {
+        s.parse().expect(s)
+    }
+
+    fn key(key: Key) -> Input {
+        Input { key, ..Default::default() }
+    }
+
+    fn ctrl(key: Key) -> Input {
+        Input {
+            key,
+            ctrl: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parses_key_bindings() {
+        assert_eq!(
+            binding("ctrl+x"),
+            KeyBinding {
+                key: Key::Char('x'),
+                ctrl: true,
+                alt: false,
+                shift: false,
+            }
+        );
+        assert_eq!(binding("Ctrl+Alt+X"), binding("ctrl+alt+X"));
+        assert_eq!(binding("F5").key, Key::F(5));
+        assert_eq!(binding("f12").key, Key::F(12));
+        assert_eq!(binding("f").key, Key::Char('f'));
+        assert_eq!(binding("space").key, Key::Char(' '));
+        assert_eq!(binding("PageDown").key, Key::PageDown);
+        assert!(binding("shift+pageup").shift);
+        assert_eq!(binding("ctrl++").key, Key::Char('+'));
+        assert!(binding("ctrl++").ctrl);
+        assert_eq!(binding("+").key, Key::Char('+'));
+        // Characters keep their case
+        assert_eq!(binding("G").key, Key::Char('G'));
+        assert_eq!(binding("g").key, Key::Char('g'));
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(matches!("".parse::<KeyBinding>(), Err(KeyBindingError::UnknownKey(_))));
+        assert!(matches!("escape".parse::<KeyBinding>(), Err(KeyBindingError::UnknownKey(key)) if key == "escape"));
+        assert!(matches!("fx".parse::<KeyBinding>(), Err(KeyBindingError::UnknownKey(_))));
+        assert!(matches!("ctrl+".parse::<KeyBinding>(), Err(KeyBindingError::UnknownKey(_))));
+        assert!(matches!("hyper+x".parse::<KeyBinding>(), Err(KeyBindingError::UnknownModifier(m)) if m == "hyper"));
+    }
+
+    #[test]
+    fn displays_key_bindings() {
+        assert_eq!(binding("ctrl+x").to_string(), "Ctrl+X");
+        assert_eq!(binding("space").to_string(), "Space");
+        assert_eq!(binding("f5").to_string(), "F5");
+        assert_eq!(binding("shift+pageup").to_string(), "Shift+PageUp");
+    }
+
+    #[test]
+    fn matches_inputs() {
+        assert!(binding("ctrl+x").matches(&ctrl(Key::Char('x'))));
+        assert!(!binding("ctrl+x").matches(&key(Key::Char('x'))));
+        assert!(!binding("x").matches(&ctrl(Key::Char('x'))));
+        // Shift is part of the character, but not of other keys
+        let question_mark = Input {
+            shift: true,
+            ..key(Key::Char('?'))
+        };
+        assert!(binding("?").matches(&question_mark));
+        assert!(!binding("pageup").matches(&Input {
+            shift: true,
+            ..key(Key::PageUp)
+        }));
+    }
+
+    #[test]
+    fn edits_text_only_without_modifiers() {
+        assert!(KeyBinding::edits_text(&key(Key::Char('a'))));
+        assert!(KeyBinding::edits_text(&key(Key::Char(' '))));
+        assert!(KeyBinding::edits_text(&Input {
+            shift: true,
+            ..key(Key::Char('A'))
+        }));
+        for cursor in [Key::Left, Key::Right, Key::Home, Key::End] {
+            assert!(KeyBinding::edits_text(&key(cursor)));
+            assert!(!KeyBinding::edits_text(&ctrl(cursor)));
+        }
+
+        assert!(!KeyBinding::edits_text(&ctrl(Key::Char('a'))));
+        assert!(!KeyBinding::edits_text(&Input {
+            alt: true,
+            ..key(Key::Char('a'))
+        }));
+        assert!(!KeyBinding::edits_text(&Input {
+            shift: true,
+            ..key(Key::Home)
+        }));
+        for other in [Key::Up, Key::Down, Key::Enter, Key::Esc, Key::Tab, Key::F(5)] {
+            assert!(!KeyBinding::edits_text(&key(other)));
+        }
+    }
+
+    #[test]
+    fn user_bindings_replace_the_preset() {
+        let settings = KeySettings {
+            preset: Preset::Default,
+            bindings: HashMap::from([(Action::Fetch, vec!["ctrl+g".to_string(), "nonsense".to_string()])]),
+        };
+        let keymap = KeyMap::new(&settings);
+
+        assert_eq!(keymap.action(&ctrl(Key::Char('g'))), Some(Action::Fetch));
+        assert_eq!(keymap.action(&key(Key::Enter)), None);
+        assert_eq!(keymap.keys(Action::Fetch), vec![binding("ctrl+g")]);
+        assert_eq!(keymap.action(&key(Key::Char(' '))), Some(Action::ToggleDetails));
+
+        let vim = KeyMap::preset(Preset::Vim);
+        assert_eq!(vim.action(&key(Key::Char('G'))), Some(Action::End));
+        assert_eq!(vim.action(&key(Key::Char('g'))), Some(Action::Home));
+    }
+}
@@ -0,0 +1,150 @@
+use crate::util::data_dir;
+use chrono::{DateTime, Local};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+/// Number of entries kept in memory for the log pane
+const SCROLLBACK_LEN: usize = 1000;
+/// Size at which the log file is rotated
+const MAX_LOG_FILE_SIZE: u64 = 1024 * 1024;
+/// Number of rotated log files kept next to the current one
+const ROTATED_FILES: usize = 3;
+const LOG_FILE_NAME: &str = "arcane.log";
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub time: DateTime<Local>,
+    pub level: Level,
+    pub message: String,
+}
+
+/// Shared scrollback of all log entries, newest last
+#[derive(Debug, Clone, Default)]
+pub struct LogBuffer {
+    entries: Arc<Mutex<VecDeque<LogEntry>>>,
+}
+
+impl LogBuffer {
+    pub fn entries(&self) -> Vec<LogEntry> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn push(&self, entry: LogEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == SCROLLBACK_LEN {
+            entries.pop_front();
+        }
+
+        entries.push_back(entry);
+    }
+}
+
+/// Routes all diagnostics into the in-app log pane and a rotating file, as writing to stdout would garble the TUI.
+struct Logger {
+    buffer: LogBuffer,
+    file: Mutex<Option<RotatingFile>>,
+}
+
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Installs the logger, returns the buffer backing the log pane
+pub fn init() -> LogBuffer {
+    let logger = LOGGER.get_or_init(|| Logger {
+        buffer: LogBuffer::default(),
+        file: Mutex::new(RotatingFile::open(data_dir().join("logs").join(LOG_FILE_NAME)).ok()),
+    });
+
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+
+    logger.buffer.clone()
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Dependencies like reqwest log as well, we are only interested in our own messages
+        metadata.target().starts_with(env!("CARGO_PKG_NAME")) && metadata.level() <= Level::Info
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let entry = LogEntry {
+            time: Local::now(),
+            level: record.level(),
+            message: record.args().to_string(),
+        };
+
+        if let Some(file) = self.file.lock().unwrap().as_mut() {
+            // There is nowhere left to report a failing log file to
+            let _ = file.write_entry(&entry);
+        }
+
+        self.buffer.push(entry);
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.file.lock().unwrap().as_mut() {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self { path, file, size })
+    }
+
+    fn write_entry(&mut self, entry: &LogEntry) -> io::Result<()> {
+        if self.size >= MAX_LOG_FILE_SIZE {
+            self.rotate()?;
+        }
+
+        let line = format!(
+            "{} [{:<5}] {}\n",
+            entry.time.format("%Y-%m-%d %H:%M:%S"),
+            entry.level,
+            entry.message
+        );
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+
+        Ok(())
+    }
+
+    /// Shifts `arcane.log` to `arcane.log.1`, `arcane.log.1` to `arcane.log.2` and so on
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotated = |n: usize| PathBuf::from(format!("{}.{n}", self.path.display()));
+
+        let _ = fs::remove_file(rotated(ROTATED_FILES));
+        for n in (1..ROTATED_FILES).rev() {
+            let _ = fs::rename(rotated(n), rotated(n + 1));
+        }
+        fs::rename(&self.path, rotated(1))?;
+
+        *self = Self::open(self.path.clone())?;
+        Ok(())
+    }
+}
@@ -1,32 +1,88 @@
+use clap::Parser;
+use cli::Cli;
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture},
+    event::{DisableMouseCapture, EnableMouseCapture, Event, MouseButton, MouseEvent, MouseEventKind},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use fuzzywuzzy::fuzz::ratio;
+use download::{DownloadState, DownloadSummary};
+use errors::{QueryError, SyncError};
+use export::ExportFormat;
+use keymap::{Action, KeyBinding, KeyMap};
+use log::Level;
+use logger::LogBuffer;
+use mirror::{MirrorReport, Previous};
 use parser::parser::{Asset, AssetFetcher};
+use preview::Preview;
+use query::Query;
+use queue::{ItemState, SavedQueue};
 use ratatui::{
     backend::CrosstermBackend,
     buffer::Buffer,
-    layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style, Stylize},
-    text::Line,
-    widgets::{Block, BorderType, Borders, HighlightSpacing, List, ListItem, ListState, StatefulWidget, Widget},
+    layout::{Constraint, Direction, Layout, Margin, Position, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{
+        Block, BorderType, Borders, Clear, Gauge, HighlightSpacing, List, ListItem, ListState, Paragraph, Sparkline, StatefulWidget, Widget,
+    },
     Terminal,
 };
-use revision_checker::Revision;
+use search::{fuzzy_match, FuzzyMatch, MIN_SCORE};
+use settings::Settings;
+use sort::Sort;
 use std::{
     cmp::Reverse,
-    collections::VecDeque,
+    collections::{HashMap, HashSet, VecDeque},
     io::{self, Write},
+    path::PathBuf,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
+use tokio::sync::oneshot::{self, error::TryRecvError};
+use tree::{assets_under, AssetTree, DirSummary, TreeRow};
 use tui_textarea::{Input, Key, TextArea};
 
+mod cli;
+mod download;
 pub mod errors;
+mod export;
+mod hooks;
+mod http;
+mod index_cache;
+mod keymap;
+mod logger;
+mod manifest;
+mod mirror;
 mod parser;
+mod preview;
+mod query;
+mod queue;
 mod revision_checker;
+mod sanitize;
+mod search;
+mod settings;
+mod sort;
+mod startup;
+mod store;
+mod texture;
+mod throttle;
+mod tree;
 pub mod util;
+mod verify;
+mod wad;
+mod watch;
 
 const VERSION: &str = "1.0.1";
+/// Height of the log pane, including its border
+const LOG_PANE_HEIGHT: u16 = 10;
+/// How long to wait for input before redrawing, so download progress keeps moving
+const TICK_RATE: Duration = Duration::from_millis(100);
+/// Two clicks on the same row within this time count as a double-click
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+/// Rows moved per mouse wheel step
+const SCROLL_STEP: usize = 3;
+/// How often the file of the shown preview is checked for changes
+const PREVIEW_CHECK_INTERVAL: Duration = Duration::from_secs(1);
 
 /// This struct holds the current state of the app.
 struct App {
@@ -35,7 +91,66 @@ struct App {
     layout: Layout,
     inner_layout: Layout,
     inner_layout_extended_info: Layout,
+    inner_layout_preview: Layout,
     extended_info: bool,
+    /// Preview of the selected asset, if it was downloaded already
+    preview: Option<Preview>,
+    /// The preview being loaded in the background, see `poll_preview`
+    preview_load: Option<(PathBuf, oneshot::Receiver<io::Result<Preview>>)>,
+    /// The file last looked at for a preview, and when
+    preview_checked: Option<(PathBuf, Instant)>,
+    view: View,
+    tree: AssetTree,
+    logs: LogBuffer,
+    show_logs: bool,
+    /// Number of entries the log pane is scrolled up from the newest one
+    log_scroll: usize,
+    focus: Focus,
+    /// Areas of the last frame, used to find out what was clicked
+    areas: Areas,
+    last_click: Option<(Instant, usize)>,
+    keymap: KeyMap,
+    popup: Option<Popup>,
+    /// Progress of the running mirror
+    mirror: Option<Arc<Mutex<MirrorReport>>>,
+    /// The revision to sync from while it is looked up, see `poll_previous`
+    previous_lookup: Option<oneshot::Receiver<Result<Previous, SyncError>>>,
+}
+
+/// Overlays waiting for the next key press
+enum Popup {
+    Help,
+    /// Asking for the format to export the search results in
+    Export,
+    /// Asking to confirm mirroring the whole revision
+    Mirror,
+    /// Asking to confirm syncing the revision from an earlier one
+    Sync(Previous),
+    MirrorReport(MirrorReport),
+    /// Asking whether to continue the downloads left in the queue by the last session
+    ResumeQueue(SavedQueue),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Search,
+    List,
+}
+
+#[derive(Default)]
+struct Areas {
+    list: Rect,
+    details: Option<Rect>,
+    logs: Option<Rect>,
+    search: Rect,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum View {
+    /// Flat, sorted list of all assets
+    List,
+    /// Assets folded into their directories
+    Tree,
 }
 
 #[derive(Clone)]
@@ -45,18 +160,37 @@ struct AssetList {
     pub filtered_items: Vec<Asset>,
     pub last_selected: Option<usize>,
     selected_assets: Option<Asset>,
+    /// Fuzzy match results of the current query, keyed by filename
+    matches: HashMap<String, FuzzyMatch>,
+    /// Filenames of all assets marked for a batch download
+    marked: HashSet<String>,
+    sort: Sort,
+    /// Changes together with `filtered_items`, so the tree view only rebuilds its rows when needed
+    version: u64,
 }
 
 #[tokio::main]
-async fn main() -> io::Result<()> {
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let logs = logger::init();
+
+    let settings = Settings::load();
+    http::init(&settings.http)?;
+    throttle::set_limit(settings.downloads.rate_limit);
+
+    if let Some(command) = cli.command {
+        return cli::run(command, &settings).await;
+    }
+
     let mut terminal = init_terminal()?;
 
-    // TODO: Move to own function??
-    let revision = Revision::check().await.unwrap();
-    let mut asset_fetcher = AssetFetcher::new(revision.clone());
-    asset_fetcher.load_index().await;
+    if let Some(mut asset_fetcher) = startup::run(&mut terminal)? {
+        if let Err(e) = asset_fetcher.configure(&settings) {
+            log::error!("Failed to use the object store, saving assets to their revision folder only: {e}");
+        }
 
-    App::new(AssetList::with_items(asset_fetcher.clone().assets), asset_fetcher).run(&mut terminal)?;
+        App::new(AssetList::with_items(asset_fetcher.clone().assets), asset_fetcher, logs, &settings).run(&mut terminal)?;
+    }
 
     restore_terminal(&mut terminal)?;
 
@@ -81,10 +215,18 @@ fn restore_terminal<W: Write>(terminal: &mut Terminal<CrosstermBackend<W>>) -> i
 }
 
 impl App {
-    fn new(assets: AssetList, asset_fetcher: AssetFetcher) -> Self {
+    fn new(assets: AssetList, asset_fetcher: AssetFetcher, logs: LogBuffer, settings: &Settings) -> Self {
         let layout = Layout::default().constraints([Constraint::Min(1), Constraint::Length(3)].as_slice());
         let inner_layout = Layout::new(Direction::Horizontal, [Constraint::Percentage(100)]);
         let inner_layout_extended_info = Layout::new(Direction::Horizontal, [Constraint::Percentage(75), Constraint::Percentage(25)]);
+        let inner_layout_preview = Layout::new(Direction::Horizontal, [Constraint::Percentage(55), Constraint::Percentage(45)]);
+
+        // Restored items wait in the queue until the user decides, so they are saved again if the app is closed before
+        asset_fetcher.queue.persist();
+        let popup = queue::restore(&asset_fetcher.revision).map(|saved| {
+            asset_fetcher.queue.restore(saved.clone());
+            Popup::ResumeQueue(saved)
+        });
 
         Self {
             assets,
@@ -93,26 +235,653 @@ impl App {
             asset_fetcher,
             extended_info: false,
             inner_layout_extended_info,
+            inner_layout_preview,
+            preview: None,
+            preview_load: None,
+            preview_checked: None,
+            view: View::List,
+            tree: AssetTree::default(),
+            logs,
+            show_logs: false,
+            log_scroll: 0,
+            focus: Focus::Search,
+            areas: Areas::default(),
+            last_click: None,
+            keymap: KeyMap::new(&settings.keys),
+            popup,
+            mirror: None,
+            previous_lookup: None,
         }
     }
 
-    /// Changes the status of the selected `ListItem`
-    fn change_status(&mut self) {
-        if let Some(nth) = self.assets.state.selected() {
-            if let Some(asset) = self.assets.filtered_items.get_mut(nth) {
+    /// Runs the action bound to a key, returns `false` if it did not apply in the current state
+    fn perform(&mut self, action: Action) -> bool {
+        let page = self.areas.list.height.max(1) as isize;
+        // Moving through the list hands it the focus, so Space toggles the details instead of being typed
+        if matches!(action, Action::Up | Action::Down | Action::PageUp | Action::PageDown) {
+            self.focus = Focus::List;
+        }
+
+        match action {
+            Action::Quit => {}
+            Action::Up => self.previous(),
+            Action::Down => self.next(),
+            Action::PageUp => self.select_relative(-page),
+            Action::PageDown => self.select_relative(page),
+            Action::Home => self.select_relative(isize::MIN),
+            Action::End => self.select_relative(isize::MAX),
+            Action::Fetch => self.change_status(),
+            Action::ToggleDetails => self.toggle_info(),
+            Action::Mark => self.toggle_mark(),
+            Action::MarkAll => self.assets.toggle_mark_all(),
+            Action::ClearMarks => self.assets.marked.clear(),
+            Action::FetchMarked => self.fetch_marked(),
+            Action::FocusSearch => self.focus = Focus::Search,
+            Action::FocusList => self.focus = Focus::List,
+            Action::ToggleView => self.toggle_view(),
+            Action::Expand if self.view == View::Tree => self.expand(),
+            Action::Collapse if self.view == View::Tree => self.collapse(),
+            Action::Expand | Action::Collapse => return false,
+            Action::ToggleLogs => self.toggle_logs(),
+            Action::ScrollLogsUp => self.scroll_logs(true),
+            Action::ScrollLogsDown => self.scroll_logs(false),
+            Action::CycleSort => self.assets.cycle_sort_key(),
+            Action::ReverseSort => self.assets.reverse_sort(),
+            Action::Export => self.popup = Some(Popup::Export),
+            Action::Mirror | Action::Sync if self.mirror.is_some() => {
+                log::warn!("A mirror of {} is running already", self.asset_fetcher.revision)
+            }
+            Action::Mirror => self.popup = Some(Popup::Mirror),
+            Action::Sync => self.find_previous(),
+            Action::PauseDownload => self.pause_selected(),
+            Action::CancelDownload => self.cancel_selected(),
+            Action::QueueEarlier => self.move_selected(-1),
+            Action::QueueLater => self.move_selected(1),
+            Action::LowerRateLimit => set_rate_limit(throttle::lower_limit(throttle::limit())),
+            Action::RaiseRateLimit => set_rate_limit(throttle::raise_limit(throttle::limit())),
+            Action::Help => self.popup = Some(Popup::Help),
+        }
+
+        true
+    }
+
+    fn row_count(&self) -> usize {
+        match self.view {
+            View::List => self.assets.filtered_items.len(),
+            View::Tree => self.tree_rows().len(),
+        }
+    }
+
+    /// Moves the selection by `delta` rows, without wrapping around
+    fn select_relative(&mut self, delta: isize) {
+        let len = self.row_count();
+        if len == 0 {
+            return;
+        }
+
+        let current = match self.view {
+            View::List => self.assets.state.selected(),
+            View::Tree => self.tree.state.selected(),
+        }
+        .unwrap_or(0);
+
+        let row = (current as isize).saturating_add(delta).clamp(0, len as isize - 1);
+        self.select(row as usize);
+    }
+
+    fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let position = Position::new(mouse.column, mouse.row);
+        let on_logs = self.areas.logs.is_some_and(|area| area.contains(position));
+        let on_details = self.areas.details.is_some_and(|area| area.contains(position));
+
+        match mouse.kind {
+            MouseEventKind::ScrollUp if on_logs => self.scroll_logs(true),
+            MouseEventKind::ScrollDown if on_logs => self.scroll_logs(false),
+            MouseEventKind::ScrollUp => (0..SCROLL_STEP).for_each(|_| self.previous()),
+            MouseEventKind::ScrollDown => (0..SCROLL_STEP).for_each(|_| self.next()),
+            MouseEventKind::Down(MouseButton::Left) if self.areas.search.contains(position) => self.focus = Focus::Search,
+            MouseEventKind::Down(MouseButton::Left) if on_details => self.toggle_info(),
+            MouseEventKind::Down(button) if self.areas.list.contains(position) => {
+                self.focus = Focus::List;
+
+                let Some(row) = self.row_at(position) else {
+                    return;
+                };
+                self.select(row);
+
+                match button {
+                    MouseButton::Left => {
+                        let double_click = self
+                            .last_click
+                            .is_some_and(|(time, last_row)| last_row == row && time.elapsed() < DOUBLE_CLICK_TIME);
+
+                        if double_click {
+                            self.last_click = None;
+                            self.change_status();
+                        } else {
+                            self.last_click = Some((Instant::now(), row));
+                        }
+                    }
+                    MouseButton::Right => self.toggle_info(),
+                    MouseButton::Middle => self.toggle_mark(),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Index of the list row at `position`, taking the scroll offset of the list into account
+    fn row_at(&self, position: Position) -> Option<usize> {
+        let offset = match self.view {
+            View::List => self.assets.state.offset(),
+            View::Tree => self.tree.state.offset(),
+        };
+        let len = self.row_count();
+
+        let row = offset + (position.y - self.areas.list.y) as usize;
+        (row < len).then_some(row)
+    }
+
+    fn select(&mut self, row: usize) {
+        match self.view {
+            View::List => self.assets.select(row),
+            View::Tree => {
+                self.tree.state.select(Some(row));
+                self.sync_tree_selection();
+            }
+        }
+    }
+
+    /// Footer hint showing how to quit and how to open the help
+    fn key_hint(&self) -> String {
+        let key = |action| match self.keymap.keys(action).first() {
+            Some(key) => key.to_string(),
+            None => "-".to_string(),
+        };
+
+        format!(" Press [{}] to quit, [{}] for help ", key(Action::Quit), key(Action::Help))
+    }
+
+    /// Exports the current search results, which are all assets if nothing was searched
+    fn export(&self, format: ExportFormat) {
+        let revision = self.asset_fetcher.to_revision();
+        let assets = &self.assets.filtered_items;
+        let filtered = assets.len() != self.assets.items.len();
+        let path = export::default_path(&revision.revision, format, filtered);
+
+        match export::export(assets, &revision, format, &path) {
+            Ok(()) => log::info!("Exported {} assets as {format} to {}", assets.len(), path.display()),
+            Err(e) => log::error!("Failed to export the assets to {}: {e}", path.display()),
+        }
+    }
+
+    /// Handles the key press closing `popup`
+    fn answer_popup(&mut self, popup: Popup, input: &Input) {
+        match (popup, input.key) {
+            (Popup::Export, Key::Char('j')) => self.export(ExportFormat::Json),
+            (Popup::Export, Key::Char('c')) => self.export(ExportFormat::Csv),
+            (Popup::Export, Key::Char('s')) => self.export(ExportFormat::Sqlite),
+            (Popup::Mirror, Key::Char('y')) => self.start_mirror(None),
+            (Popup::Sync(previous), Key::Char('y')) => self.start_mirror(Some(previous)),
+            (Popup::ResumeQueue(saved), Key::Char('y')) => self.resume_queue(&saved),
+            (Popup::ResumeQueue(_), Key::Char('n')) => {
+                self.asset_fetcher.queue.discard_restored();
+                log::info!("Discarded the downloads left in the queue");
+            }
+            _ => {}
+        }
+    }
+
+    fn render_popup(&self, popup: &Popup, area: Rect, buf: &mut Buffer) {
+        match popup {
+            Popup::Help => render_popup(
+                " Key bindings ".to_string(),
+                " Press any key to close ",
+                help_lines(&self.keymap),
+                72,
+                area,
+                buf,
+            ),
+            Popup::Export => render_popup(
+                format!(" Export {} assets ", self.assets.filtered_items.len()),
+                " Any other key cancels ",
+                vec![Line::from(" [J] JSON   [C] CSV   [S] SQLite").centered()],
+                60,
+                area,
+                buf,
+            ),
+            Popup::Mirror => {
+                let size: i64 = self.assets.items.iter().map(|asset| asset.size).sum();
+                let lines = vec![
+                    Line::from(format!(
+                        " Download all {} assets ({}) into assets/{}/?",
+                        self.assets.items.len(),
+                        bytes_to_human_readable(size),
+                        self.asset_fetcher.revision
+                    )),
+                    Line::from(" Files which are verified already are skipped."),
+                    Line::from(""),
+                    Line::from(" [Y] Start mirroring").centered(),
+                ];
+                render_popup(
+                    format!(" Mirror {} ", self.asset_fetcher.revision),
+                    " Any other key cancels ",
+                    lines,
+                    72,
+                    area,
+                    buf,
+                );
+            }
+            Popup::Sync(previous) => {
+                let unchanged: Vec<&Asset> = self
+                    .assets
+                    .items
+                    .iter()
+                    .filter(|asset| previous.unchanged(asset).is_some())
+                    .collect();
+                let unchanged_size: i64 = unchanged.iter().map(|asset| asset.size).sum();
+                let changed_size: i64 = self.assets.items.iter().map(|asset| asset.size).sum::<i64>() - unchanged_size;
+
+                let lines = vec![
+                    Line::from(format!(
+                        " {} unchanged assets ({}) are linked from assets/{}/,",
+                        unchanged.len(),
+                        bytes_to_human_readable(unchanged_size),
+                        previous.revision
+                    )),
+                    Line::from(format!(
+                        " the other {} ({}) are downloaded.",
+                        self.assets.items.len() - unchanged.len(),
+                        bytes_to_human_readable(changed_size)
+                    )),
+                    Line::from(""),
+                    Line::from(" [Y] Start syncing").centered(),
+                ];
+                render_popup(
+                    format!(" Sync {} from {} ", self.asset_fetcher.revision, previous.revision),
+                    " Any other key cancels ",
+                    lines,
+                    72,
+                    area,
+                    buf,
+                );
+            }
+            Popup::MirrorReport(report) => render_popup(
+                format!(" Mirrored {} ", report.revision),
+                " Press any key to close ",
+                mirror_report_lines(report),
+                100,
+                area,
+                buf,
+            ),
+            Popup::ResumeQueue(saved) => render_popup(
+                " Resume downloads ".to_string(),
+                " Any other key asks again next time ",
+                resume_queue_lines(saved),
+                80,
+                area,
+                buf,
+            ),
+        }
+    }
+
+    /// Queues the downloads of the last session again, in their old order
+    fn resume_queue(&mut self, saved: &SavedQueue) {
+        let by_filename: HashMap<&str, &Asset> = self.assets.items.iter().map(|asset| (asset.filename.as_str(), asset)).collect();
+        let assets: Vec<Asset> = saved
+            .items
+            .iter()
+            .filter_map(|item| {
+                let asset = by_filename.get(item.filename.as_str()).copied();
+                if asset.is_none() {
+                    log::warn!("Not resuming {}, it is not in the file list anymore", item.filename);
+                }
+                asset.cloned()
+            })
+            .collect();
+
+        log::info!("Resuming {} downloads", assets.len());
+        self.fetch_all(assets);
+    }
+
+    fn pause_selected(&mut self) {
+        for asset in self.selected_assets() {
+            match self.asset_fetcher.queue.toggle_pause(&asset.filename) {
+                Some(true) => log::info!("Paused {}", asset.filename),
+                Some(false) => log::info!("Resumed {}", asset.filename),
+                None => log::warn!("{} is not waiting in the queue", asset.filename),
+            }
+        }
+    }
+
+    /// Cancels the selected downloads, whether they are queued or running
+    fn cancel_selected(&mut self) {
+        for asset in self.selected_assets() {
+            if !self.asset_fetcher.cancel(&asset) {
+                log::warn!("{} is not being downloaded", asset.filename);
+                continue;
+            }
+
+            let saved = self.asset_fetcher.local_path(&asset).is_some_and(|path| path.is_file());
+            self.assets.set_fetched(&asset.filename, saved);
+        }
+    }
+
+    fn move_selected(&mut self, delta: isize) {
+        let mut assets = self.selected_assets();
+        // Moving a directory's assets back one by one would swap them among themselves, starting with the last keeps their order
+        if delta > 0 {
+            assets.reverse();
+        }
+
+        for asset in assets {
+            match self.asset_fetcher.queue.move_by(&asset.filename, delta) {
+                Some(position) => log::info!("{} is #{position} in the queue now", asset.filename),
+                None => log::warn!("{} is not waiting in the queue", asset.filename),
+            }
+        }
+    }
+
+    /// Looks for the revision to sync from in the background, as that parses its whole file list
+    fn find_previous(&mut self) {
+        let (sender, receiver) = oneshot::channel();
+        let revision = self.asset_fetcher.revision.clone();
+        tokio::task::spawn_blocking(move || {
+            let _ = sender.send(Previous::find(&revision));
+        });
+
+        self.previous_lookup = Some(receiver);
+    }
+
+    /// Asks to confirm the sync once the revision to sync from was found
+    fn poll_previous(&mut self) {
+        let Some(receiver) = self.previous_lookup.as_mut() else {
+            return;
+        };
+
+        match receiver.try_recv() {
+            Err(TryRecvError::Empty) => return,
+            Ok(Ok(previous)) => self.popup = Some(Popup::Sync(previous)),
+            Ok(Err(e)) => log::warn!("Cannot sync {}: {e}", self.asset_fetcher.revision),
+            Err(TryRecvError::Closed) => log::error!("Failed to look for a revision to sync {} from", self.asset_fetcher.revision),
+        }
+        self.previous_lookup = None;
+    }
+
+    /// Mirrors every asset of the revision in the background, see `poll_mirror`.
+    /// With a `previous` revision, its unchanged files are linked instead of downloaded.
+    fn start_mirror(&mut self, previous: Option<Previous>) {
+        let report = Arc::new(Mutex::new(MirrorReport::default()));
+        let fetcher = self.asset_fetcher.clone();
+        let assets = self.assets.items.clone();
+        if let Err(e) = mirror::check_space(&fetcher, &assets, previous.as_ref()) {
+            log::error!("Not mirroring {}: {e}", fetcher.revision);
+            return;
+        }
+
+        let shared = report.clone();
+        tokio::spawn(async move {
+            let result = mirror::mirror(&fetcher, &assets, previous.as_ref(), |progress| {
+                *shared.lock().unwrap() = progress.clone()
+            })
+            .await;
+            *shared.lock().unwrap() = result;
+        });
+
+        self.mirror = Some(report);
+    }
+
+    /// Shows the report once the mirror finished and marks every mirrored asset as fetched
+    fn poll_mirror(&mut self) {
+        let Some(report) = self.mirror.as_ref().map(|report| report.lock().unwrap().clone()) else {
+            return;
+        };
+        if !report.finished {
+            return;
+        }
+
+        let failed: HashSet<&str> = report.failed.iter().map(|(filename, _)| filename.as_str()).collect();
+        for asset in self.assets.items.iter_mut().chain(self.assets.filtered_items.iter_mut()) {
+            if !failed.contains(asset.filename.as_str()) {
                 asset.already_fetched = true;
-                self.asset_fetcher.fetch_asset(asset);
+            }
+        }
+        self.assets.version += 1;
+
+        self.mirror = None;
+        self.popup = Some(Popup::MirrorReport(report));
+    }
+
+    fn toggle_logs(&mut self) {
+        self.show_logs = !self.show_logs;
+        self.log_scroll = 0;
+    }
+
+    fn scroll_logs(&mut self, up: bool) {
+        const PAGE: usize = 5;
+        let max_scroll = self.logs.entries().len().saturating_sub(1);
+
+        self.log_scroll = match up {
+            true => (self.log_scroll + PAGE).min(max_scroll),
+            false => self.log_scroll.saturating_sub(PAGE),
+        };
+    }
+
+    /// Changes the status of the selected `ListItem`, or of every asset below the selected directory
+    fn change_status(&mut self) {
+        self.fetch_all(self.selected_assets());
+    }
+
+    /// The selected asset, or every asset below the selected directory
+    fn selected_assets(&self) -> Vec<Asset> {
+        match self.view {
+            View::List => self
+                .assets
+                .state
+                .selected()
+                .and_then(|nth| self.assets.filtered_items.get(nth))
+                .cloned()
+                .into_iter()
+                .collect(),
+            View::Tree => match self.tree.selected(&self.tree_rows()) {
+                Some(TreeRow::Dir { summary, .. }) => assets_under(&self.assets.filtered_items, &summary.path).cloned().collect(),
+                Some(TreeRow::File { asset, .. }) => vec![asset.clone()],
+                None => Vec::new(),
+            },
+        }
+    }
+
+    /// Starts downloading `assets`, unless there is not enough disk space for them. Assets with a rejected filename are skipped.
+    fn fetch_all(&mut self, assets: Vec<Asset>) {
+        if let Err(e) = self.asset_fetcher.check_space(&assets) {
+            log::error!("Not fetching {} assets: {e}", assets.len());
+            return;
+        }
+
+        for asset in assets {
+            if let Some(reason) = &asset.rejected {
+                log::warn!("Not fetching {}: {reason}", asset.filename);
+                continue;
+            }
+
+            if !self.asset_fetcher.fetch_asset(&asset) {
+                log::info!("{} is queued already", asset.filename);
+            }
+            self.assets.set_fetched(&asset.filename, true);
+        }
+    }
 
-                // ngughh
-                if let Some(item) = self.assets.items.iter_mut().find(|a| a.filename == asset.filename) {
-                    item.already_fetched = true;
+    /// (Un-)marks the selected asset, or every asset below the selected directory
+    fn toggle_mark(&mut self) {
+        match self.view {
+            View::List => self.assets.toggle_mark(),
+            View::Tree => match self.tree.selected(&self.tree_rows()) {
+                Some(TreeRow::Dir { summary, .. }) => {
+                    let filenames: Vec<String> = assets_under(&self.assets.filtered_items, &summary.path)
+                        .map(|asset| asset.filename.clone())
+                        .collect();
+                    self.assets.toggle_mark_many(&filenames);
                 }
+                Some(TreeRow::File { asset, .. }) => self.assets.toggle_mark_many(std::slice::from_ref(&asset.filename)),
+                None => {}
+            },
+        }
+    }
+
+    fn tree_rows(&self) -> Rc<[TreeRow]> {
+        self.tree.rows(&self.assets.filtered_items, self.assets.version)
+    }
+
+    fn next(&mut self) {
+        match self.view {
+            View::List => self.assets.next(),
+            View::Tree => {
+                self.tree.next(self.tree_rows().len());
+                self.sync_tree_selection();
+            }
+        }
+    }
+
+    fn previous(&mut self) {
+        match self.view {
+            View::List => self.assets.previous(),
+            View::Tree => {
+                self.tree.previous(self.tree_rows().len());
+                self.sync_tree_selection();
             }
         }
     }
 
+    fn expand(&mut self) {
+        if let Some(TreeRow::Dir { summary, .. }) = self.tree.selected(&self.tree_rows()) {
+            self.tree.expand(&summary.path);
+        }
+    }
+
+    fn collapse(&mut self) {
+        self.tree.collapse(&self.tree_rows());
+        self.sync_tree_selection();
+    }
+
+    /// Keeps the asset shown in the Details pane in sync with the selected tree row
+    fn sync_tree_selection(&mut self) {
+        self.assets.selected_assets = match self.tree.selected(&self.tree_rows()) {
+            Some(TreeRow::File { asset, .. }) => Some(asset.clone()),
+            _ => None,
+        };
+    }
+
+    fn toggle_view(&mut self) {
+        self.view = match self.view {
+            View::List => View::Tree,
+            View::Tree => View::List,
+        };
+
+        match self.view {
+            View::List => self.assets.selected_assets = self.assets.selected().cloned(),
+            View::Tree => self.sync_tree_selection(),
+        }
+    }
+
+    /// Fetches every marked asset and clears the marks afterwards
+    fn fetch_marked(&mut self) {
+        let marked: Vec<Asset> = self
+            .assets
+            .items
+            .iter()
+            .filter(|asset| self.assets.marked.contains(&asset.filename))
+            .cloned()
+            .collect();
+
+        self.fetch_all(marked);
+        self.assets.marked.clear();
+    }
+
+    /// (Re-)loads the preview in the background if another asset was selected, or the file changed on disk.
+    /// While the same asset stays selected, its file is only looked at every `PREVIEW_CHECK_INTERVAL`.
+    fn update_preview(&mut self) {
+        self.poll_preview();
+
+        let Some(path) = self.preview_path() else {
+            self.preview = None;
+            self.preview_load = None;
+            return;
+        };
+        if self.preview.as_ref().is_some_and(|preview| preview.path != path) {
+            self.preview = None;
+        }
+        if self.preview_load.as_ref().is_some_and(|(loading, _)| *loading != path) {
+            self.preview_load = None;
+        }
+
+        let recently_checked = self
+            .preview_checked
+            .as_ref()
+            .is_some_and(|(checked, at)| *checked == path && at.elapsed() < PREVIEW_CHECK_INTERVAL);
+        if recently_checked || self.preview_load.is_some() {
+            return;
+        }
+        self.preview_checked = Some((path.clone(), Instant::now()));
+
+        if !path.is_file() {
+            self.preview = None;
+            return;
+        }
+        if self.preview.as_ref().is_some_and(|preview| !preview.is_stale()) {
+            return;
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        let load = path.clone();
+        tokio::task::spawn_blocking(move || {
+            let _ = sender.send(Preview::load(&load));
+        });
+        self.preview_load = Some((path, receiver));
+    }
+
+    /// Where the selected asset is saved, unless the details are hidden or it is still being downloaded
+    fn preview_path(&self) -> Option<PathBuf> {
+        let asset = self.assets.selected_assets.as_ref().filter(|_| self.extended_info)?;
+        let downloading = self
+            .asset_fetcher
+            .downloads
+            .get(&asset.filename)
+            .is_some_and(|progress| progress.state == DownloadState::Running);
+
+        match downloading {
+            true => None,
+            false => self.asset_fetcher.local_path(asset),
+        }
+    }
+
+    /// Shows the preview once it was loaded
+    fn poll_preview(&mut self) {
+        let Some((path, receiver)) = self.preview_load.as_mut() else {
+            return;
+        };
+
+        self.preview = match receiver.try_recv() {
+            Err(TryRecvError::Empty) => return,
+            Ok(Ok(preview)) => Some(preview),
+            Ok(Err(e)) => {
+                log::warn!("Failed to preview {}: {e}", path.display());
+                None
+            }
+            Err(TryRecvError::Closed) => {
+                log::error!("Failed to preview {}", path.display());
+                None
+            }
+        };
+        self.preview_load = None;
+    }
+
     fn toggle_info(&mut self) {
-        if let Some(_) = self.assets.state.selected() {
+        let selected = match self.view {
+            View::List => self.assets.state.selected(),
+            View::Tree => self.tree.state.selected(),
+        };
+
+        if selected.is_some() {
             self.extended_info = !self.extended_info;
         } else {
             self.extended_info = false;
@@ -122,26 +891,55 @@ impl App {
     fn run(&mut self, terminal: &mut Terminal<CrosstermBackend<io::StdoutLock<'static>>>) -> io::Result<()> {
         let mut textarea = TextArea::default();
         textarea.set_cursor_line_style(Style::default());
-        textarea.set_placeholder_text("Enter a filename");
-        textarea.set_block(Block::new().border_type(BorderType::Rounded).borders(Borders::all()));
+        textarea.set_placeholder_text("Enter a filename or filters like ext:wad size>5MB fetched:no");
+        textarea.set_block(search_block(None));
         let layout = Layout::default().constraints([Constraint::Min(1), Constraint::Length(3)].as_slice());
 
         loop {
             self.draw(terminal, &layout, &mut textarea)?;
+            self.asset_fetcher.downloads.sample();
+            self.poll_mirror();
+            self.poll_previous();
+
+            if !crossterm::event::poll(TICK_RATE)? {
+                continue;
+            }
+
+            let event = crossterm::event::read()?;
+            if let Event::Mouse(mouse) = event {
+                self.handle_mouse(mouse);
+                continue;
+            }
 
-            match crossterm::event::read()?.into() {
-                Input { key: Key::Esc, .. } => break,
-                Input { key: Key::Enter, .. } => self.change_status(),
-                Input { key: Key::Up, .. } => self.assets.previous(),
-                Input { key: Key::Down, .. } => self.assets.next(),
-                Input { key: Key::Char(' '), .. } => self.toggle_info(),
-                input => {
-                    textarea.input(input);
-
-                    let content = &textarea.lines()[0].to_string();
-                    self.assets.filter_and_sort(&content);
+            let input: Input = event.into();
+            if let Some(popup) = self.popup.take() {
+                self.answer_popup(popup, &input);
+                continue;
+            }
+
+            // While the search box is focused, it gets plain characters and cursor movement no matter how they are bound
+            let typing = self.focus == Focus::Search && KeyBinding::edits_text(&input);
+            let handled = match self.keymap.action(&input) {
+                Some(Action::Quit) if !typing => {
+                    if let Err(e) = self.asset_fetcher.queue.save() {
+                        log::error!("Failed to save the download queue: {e}");
+                    }
+                    break;
                 }
+                Some(action) if !typing => self.perform(action),
+                _ => false,
+            };
+
+            if handled || (self.focus == Focus::List && !KeyBinding::is_text(&input)) {
+                continue;
             }
+
+            self.focus = Focus::Search;
+            textarea.input(input);
+
+            let content = &textarea.lines()[0].to_string();
+            let error = self.assets.filter_and_sort(content).err();
+            textarea.set_block(search_block(error.as_ref()));
         }
 
         Ok(())
@@ -154,11 +952,24 @@ impl App {
         layout: &Layout,
         textarea: &mut TextArea,
     ) -> io::Result<()> {
+        let border_style = match self.focus {
+            Focus::Search => Style::default().cyan(),
+            Focus::List => Style::default(),
+        };
+        if let Some(block) = textarea.block().cloned() {
+            textarea.set_block(block.border_style(border_style));
+        }
+
         terminal.draw(|f| {
-            f.render_widget(self, f.size());
+            f.render_widget(&mut *self, f.size());
 
             let chunks = layout.split(f.size());
+            self.areas.search = chunks[1];
             f.render_widget(textarea.widget(), chunks[1]);
+
+            if let Some(popup) = &self.popup {
+                self.render_popup(popup, f.size(), f.buffer_mut());
+            }
         })?;
 
         Ok(())
@@ -171,35 +982,83 @@ impl Widget for &mut App {
         Self: Sized,
     {
         let chunks = self.layout.split(area);
+        self.update_preview();
 
         let block = Block::new()
             .border_type(BorderType::Rounded)
             .borders(Borders::all())
             .title(Line::from(format!(" Arcane (Asset-Fetcher) v{VERSION} ")).centered())
             .title(Line::from(format!(" {} ", self.asset_fetcher.revision)).left_aligned())
-            .title(Line::from(format!(" {} assets found ", self.assets.asset_len())).right_aligned())
+            .title(
+                Line::from(format!(
+                    " {} assets found | Sorted by {} ",
+                    self.assets.asset_len(),
+                    self.assets.sort
+                ))
+                .right_aligned(),
+            )
             .title_bottom(Line::from(" © Phill030 (Revive101) ").left_aligned())
-            .title_bottom(Line::from(" Press [ESC] to abort ").right_aligned());
+            .title_bottom(Line::from(self.assets.marked_summary()).centered())
+            .title_bottom(Line::from(self.key_hint()).right_aligned());
 
         block.render(chunks[0], buf);
 
+        let summary = self.asset_fetcher.downloads.summary();
+        let mut body = chunks[0].inner(&Margin::new(1, 1));
+        if summary.active > 0 || summary.queued > 0 {
+            let split = Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).split(body);
+            body = split[0];
+            self.render_status_bar(&summary, split[1], buf);
+        }
+
+        self.areas.logs = None;
+        if self.show_logs {
+            let split = Layout::vertical([Constraint::Min(1), Constraint::Length(LOG_PANE_HEIGHT)]).split(body);
+            body = split[0];
+            self.areas.logs = Some(split[1]);
+            self.render_logs(split[1], buf);
+        }
+
         // List items
-        let items: Vec<ListItem> = self
-            .assets
-            .filtered_items
-            .iter()
-            .enumerate()
-            .map(|(i, asset)| asset.to_list_item(i))
-            .collect();
+        let tree_rows = match self.view {
+            View::List => Rc::from([]),
+            View::Tree => self.tree_rows(),
+        };
+        let items: Vec<ListItem> = match self.view {
+            View::List => self
+                .assets
+                .filtered_items
+                .iter()
+                .map(|asset| {
+                    asset.to_list_item(
+                        self.assets.matches.get(&asset.filename),
+                        self.assets.marked.contains(&asset.filename),
+                    )
+                })
+                .collect(),
+            View::Tree => tree_rows.iter().map(|row| row.to_list_item(&self.assets.marked)).collect(),
+        };
 
-        let list_block = match self.extended_info {
-            true => Block::new()
+        let list_block = match (self.extended_info, self.view) {
+            (true, View::List) => Block::new()
                 .border_type(BorderType::Rounded)
                 .borders(Borders::all())
                 .title("Assets"),
-            false => Block::new().borders(Borders::NONE),
+            (true, View::Tree) => Block::new()
+                .border_type(BorderType::Rounded)
+                .borders(Borders::all())
+                .title("Assets (Tree)"),
+            (false, _) => Block::new().borders(Borders::NONE),
         };
 
+        let inner = match (self.extended_info, &self.preview) {
+            (true, Some(_)) => self.inner_layout_preview.split(body),
+            (true, None) => self.inner_layout_extended_info.split(body),
+            (false, _) => self.inner_layout.split(body),
+        };
+        self.areas.list = list_block.inner(inner[0]);
+        self.areas.details = self.extended_info.then(|| inner[1]);
+
         let list = List::new(items)
             .block(list_block)
             .style(Style::default().fg(Color::White))
@@ -207,43 +1066,316 @@ impl Widget for &mut App {
             .highlight_symbol(">> ")
             .highlight_spacing(HighlightSpacing::Always);
 
-        let inner = match self.extended_info {
-            true => <Layout as Clone>::clone(&self.inner_layout_extended_info)
-                .margin(1)
-                .split(chunks[0]),
-            false => <Layout as Clone>::clone(&self.inner_layout).margin(1).split(chunks[0]),
+        let state = match self.view {
+            View::List => &mut self.assets.state,
+            View::Tree => &mut self.tree.state,
         };
-        StatefulWidget::render(list, inner[0], buf, &mut self.assets.state);
-
-        if self.extended_info {
-            if let Some(selected_assets) = &self.assets.selected_assets {
-                let info_items = vec![
-                    ListItem::new(format!("Name: {}", selected_assets.filename)),
-                    ListItem::new(format!("Size: {}", bytes_to_human_readable(selected_assets.size))),
-                    ListItem::new(format!("CRC: {}", selected_assets.crc)),
-                    ListItem::new(format!("HeaderCRC: {}", selected_assets.header_crc)),
-                    ListItem::new(format!("HeaderSize: {}", selected_assets.header_size)),
-                    ListItem::new(format!("CompressedHeaderSize: {}", selected_assets.compressed_header_size)),
-                ];
+        StatefulWidget::render(list, inner[0], buf, state);
 
-                let extended_info_list = List::new(info_items).block(
-                    Block::new()
-                        .border_type(BorderType::Rounded)
-                        .borders(Borders::all())
-                        .title(" Details ")
-                        .style(Style::default().fg(Color::White)),
-                );
+        if !self.extended_info {
+            return;
+        }
 
-                Widget::render(extended_info_list, inner[1], buf);
+        if let Some(TreeRow::Dir { summary, .. }) = self.tree.selected(&tree_rows) {
+            render_dir_details(summary, inner[1], buf);
+        } else if let Some(selected_assets) = &self.assets.selected_assets {
+            let mut info_items = vec![
+                ListItem::new(format!("Name: {}", selected_assets.filename)),
+                ListItem::new(format!("Size: {}", bytes_to_human_readable(selected_assets.size))),
+                ListItem::new(format!("Table: {}", selected_assets.table)),
+                ListItem::new(format!("CRC: {}", selected_assets.crc)),
+                ListItem::new(format!("HeaderCRC: {}", selected_assets.header_crc)),
+                ListItem::new(format!("HeaderSize: {}", selected_assets.header_size)),
+                ListItem::new(format!("CompressedHeaderSize: {}", selected_assets.compressed_header_size)),
+            ];
+            if let Some(reason) = &selected_assets.rejected {
+                info_items.push(ListItem::new(format!("Rejected: {reason}")).red());
             }
+
+            if let Some(matched) = self.assets.matches.get(&selected_assets.filename) {
+                info_items.push(ListItem::new(format!("Score: {}", matched.score)));
+            }
+
+            let mut details_area = inner[1];
+            if let Some(preview) = &self.preview {
+                let split = Layout::vertical([Constraint::Length(info_items.len() as u16 + 2), Constraint::Min(1)]).split(details_area);
+                details_area = split[0];
+                preview.render(split[1], buf);
+            }
+
+            if let Some(progress) = self.asset_fetcher.downloads.get(&selected_assets.filename) {
+                let split = Layout::vertical([Constraint::Min(1), Constraint::Length(3)]).split(details_area);
+                details_area = split[0];
+
+                let (title, color) = match &progress.state {
+                    DownloadState::Queued => match self.asset_fetcher.queue.get(&selected_assets.filename) {
+                        Some((position, item)) if item.paused => (format!(" Paused, #{position} in the queue "), Color::DarkGray),
+                        Some((position, _)) => (format!(" Queued, #{position} "), Color::DarkGray),
+                        None => (" Queued ".to_string(), Color::DarkGray),
+                    },
+                    DownloadState::Running => (" Downloading ".to_string(), Color::Cyan),
+                    DownloadState::Finished => (" Finished ".to_string(), Color::Green),
+                    DownloadState::Failed(reason) => (format!(" Failed: {reason} "), Color::Red),
+                };
+
+                let gauge = Gauge::default()
+                    .block(Block::new().border_type(BorderType::Rounded).borders(Borders::all()).title(title))
+                    .gauge_style(Style::default().fg(color))
+                    .ratio(progress.ratio())
+                    .label(format!(
+                        "{} / {} ({} chunks)",
+                        bytes_to_human_readable(progress.received as i64),
+                        bytes_to_human_readable(progress.total as i64),
+                        progress.chunks
+                    ));
+
+                gauge.render(split[1], buf);
+            }
+
+            let extended_info_list = List::new(info_items).block(
+                Block::new()
+                    .border_type(BorderType::Rounded)
+                    .borders(Borders::all())
+                    .title(" Details ")
+                    .style(Style::default().fg(Color::White)),
+            );
+
+            Widget::render(extended_info_list, details_area, buf);
+        }
+    }
+}
+
+/// Centred box drawn above everything else, closed by the next key press
+fn render_popup(title: String, hint: &str, lines: Vec<Line>, width: u16, area: Rect, buf: &mut Buffer) {
+    let [_, area, _] = Layout::vertical([Constraint::Fill(1), Constraint::Length(lines.len() as u16 + 2), Constraint::Fill(1)]).areas(area);
+    let [_, area, _] = Layout::horizontal([Constraint::Fill(1), Constraint::Length(width), Constraint::Fill(1)]).areas(area);
+
+    Clear.render(area, buf);
+    Paragraph::new(lines)
+        .block(
+            Block::new()
+                .border_type(BorderType::Rounded)
+                .borders(Borders::all())
+                .title(title)
+                .title_bottom(Line::from(hint.to_string()).right_aligned()),
+        )
+        .render(area, buf);
+}
+
+/// Every action and the keys currently bound to it
+fn help_lines(keymap: &KeyMap) -> Vec<Line<'static>> {
+    Action::ALL
+        .iter()
+        .filter_map(|action| {
+            let keys = keymap.keys(*action);
+            if keys.is_empty() {
+                return None;
+            }
+
+            let keys = keys.iter().map(|key| key.to_string()).collect::<Vec<_>>().join(", ");
+            Some(Line::from(vec![
+                Span::styled(format!(" {keys:<24}"), Style::default().cyan()),
+                Span::raw(action.description()),
+            ]))
+        })
+        .collect()
+}
+
+/// What is left in the queue of the last session
+fn resume_queue_lines(saved: &SavedQueue) -> Vec<Line<'static>> {
+    let mut pending = 0;
+    let mut partial = Vec::new();
+    let mut failed = Vec::new();
+    for item in &saved.items {
+        match &item.state {
+            ItemState::Pending => pending += 1,
+            ItemState::Partial { offset } => partial.push(*offset),
+            ItemState::Failed { reason } => failed.push((&item.filename, reason)),
         }
     }
+
+    let mut lines = vec![
+        Line::from(format!(
+            " {} downloads of {} were left in the queue:",
+            saved.items.len(),
+            saved.revision
+        )),
+        Line::from(format!(
+            " {pending} pending, {} partly downloaded ({} received), {} failed",
+            partial.len(),
+            bytes_to_human_readable(partial.iter().sum::<u64>() as i64),
+            failed.len()
+        )),
+    ];
+    for (filename, reason) in failed.iter().take(3) {
+        lines.push(Line::from(format!("   {filename}: {reason}")).red());
+    }
+    if failed.len() > 3 {
+        lines.push(Line::from(format!("   and {} more", failed.len() - 3)).red());
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(" [Y] Resume, retrying failed ones   [N] Discard").centered());
+    lines
+}
+
+fn set_rate_limit(limit: u64) {
+    throttle::set_limit(limit);
+    match limit {
+        0 => log::info!("Download speed is unlimited"),
+        limit => log::info!("Download speed limited to {}/s", bytes_to_human_readable(limit as i64)),
+    }
+}
+
+/// Outcome of a mirror, listing the first few failed files
+fn mirror_report_lines(report: &MirrorReport) -> Vec<Line<'static>> {
+    const MAX_FAILED: usize = 10;
+
+    let mut lines = vec![
+        Line::from(format!(
+            " Downloaded: {} ({})",
+            report.downloaded,
+            bytes_to_human_readable(report.bytes as i64)
+        )),
+        Line::from(format!(" Already verified: {}", report.skipped)),
+    ];
+    if let Some(previous) = report.linked_from() {
+        lines.push(Line::from(format!(
+            " Linked from {previous}: {} ({} saved)",
+            report.reused,
+            bytes_to_human_readable(report.bytes_saved as i64)
+        )));
+    }
+
+    lines.extend([
+        Line::from(format!(" Failed: {}", report.failed.len())).style(match report.failed.is_empty() {
+            true => Style::default(),
+            false => Style::default().red(),
+        }),
+        Line::from(format!(" Took: {}", format_duration(report.elapsed))),
+    ]);
+
+    for (filename, reason) in report.failed.iter().take(MAX_FAILED) {
+        lines.push(Line::from(format!("   {filename}: {reason}")).red());
+    }
+    if report.failed.len() > MAX_FAILED {
+        lines.push(Line::from(format!("   ... and {} more, see the log", report.failed.len() - MAX_FAILED)).red());
+    }
+
+    lines
+}
+
+/// Details pane for a directory of the tree view
+fn render_dir_details(summary: &DirSummary, area: Rect, buf: &mut Buffer) {
+    let info_items = vec![
+        ListItem::new(format!("Directory: {}/", summary.path)),
+        ListItem::new(format!("Size: {}", bytes_to_human_readable(summary.size))),
+        ListItem::new(format!("Assets: {}", summary.assets)),
+        ListItem::new(format!("Fetched: {} / {}", summary.fetched, summary.assets)),
+    ];
+
+    let extended_info_list = List::new(info_items).block(
+        Block::new()
+            .border_type(BorderType::Rounded)
+            .borders(Borders::all())
+            .title(" Details ")
+            .style(Style::default().fg(Color::White)),
+    );
+
+    Widget::render(extended_info_list, area, buf);
+}
+
+impl App {
+    /// Scrollback of everything logged so far, coloured by severity
+    fn render_logs(&self, area: Rect, buf: &mut Buffer) {
+        let entries = self.logs.entries();
+        let height = area.height.saturating_sub(2) as usize;
+        let end = entries.len().saturating_sub(self.log_scroll);
+        let start = end.saturating_sub(height);
+
+        let items: Vec<ListItem> = entries[start..end]
+            .iter()
+            .map(|entry| {
+                let color = match entry.level {
+                    Level::Error => Color::Red,
+                    Level::Warn => Color::Yellow,
+                    Level::Info => Color::White,
+                    Level::Debug | Level::Trace => Color::DarkGray,
+                };
+
+                ListItem::new(Line::from(vec![
+                    Span::styled(entry.time.format("%H:%M:%S ").to_string(), Style::default().dark_gray()),
+                    Span::styled(
+                        format!("{:<5} ", entry.level),
+                        Style::default().fg(color).add_modifier(Modifier::BOLD),
+                    ),
+                    Span::styled(entry.message.clone(), Style::default().fg(color)),
+                ]))
+            })
+            .collect();
+
+        let title = match self.log_scroll {
+            0 => " Log ".to_string(),
+            n => format!(" Log (-{n}) "),
+        };
+
+        let list = List::new(items).block(Block::new().border_type(BorderType::Rounded).borders(Borders::all()).title(title));
+        Widget::render(list, area, buf);
+    }
+
+    /// Overall progress of all running downloads, including a throughput sparkline
+    fn render_status_bar(&self, summary: &DownloadSummary, area: Rect, buf: &mut Buffer) {
+        let split = Layout::horizontal([Constraint::Min(1), Constraint::Percentage(30)]).split(area);
+
+        let eta = match summary.eta {
+            Some(eta) => format_duration(eta),
+            None => "-".to_string(),
+        };
+        let mirror = match &self.mirror {
+            Some(report) => {
+                let report = report.lock().unwrap();
+                match self.asset_fetcher.schedule().filter(|schedule| !schedule.is_open()) {
+                    Some(schedule) => format!(" Mirror waits for {schedule} |"),
+                    None => format!(" Mirroring {}/{} |", report.done(), report.total),
+                }
+            }
+            None => String::new(),
+        };
+        let limit = match throttle::limit() {
+            0 => String::new(),
+            limit => format!(" max {}/s", bytes_to_human_readable(limit as i64)),
+        };
+        let queued = match summary.queued {
+            0 => String::new(),
+            queued => format!(" {queued} queued |"),
+        };
+        let status = format!(
+            "{mirror} ⇣ {} active |{queued} {} / {} | {}/s{limit} | ETA {} ",
+            summary.active,
+            bytes_to_human_readable(summary.received as i64),
+            bytes_to_human_readable(summary.total as i64),
+            bytes_to_human_readable(summary.throughput as i64),
+            eta
+        );
+
+        Paragraph::new(status)
+            .style(Style::default().black().on_cyan())
+            .render(split[0], buf);
+
+        let history = self.asset_fetcher.downloads.history();
+        let visible = &history[history.len().saturating_sub(split[1].width as usize)..];
+        Sparkline::default()
+            .data(visible)
+            .style(Style::default().cyan())
+            .render(split[1], buf);
+    }
 }
 
 impl AssetList {
     fn with_items(items: VecDeque<Asset>) -> Self {
+        let sort = Sort::default();
         let mut items = Vec::from(items);
-        items.sort();
+        items.sort_by(|a, b| sort.compare(a, b));
 
         Self {
             state: ListState::default(),
@@ -251,10 +1383,109 @@ impl AssetList {
             items,
             last_selected: None,
             selected_assets: None,
+            matches: HashMap::new(),
+            marked: HashSet::new(),
+            sort,
+            version: 0,
+        }
+    }
+
+    fn cycle_sort_key(&mut self) {
+        self.sort.cycle_key();
+        self.apply_sort();
+    }
+
+    fn reverse_sort(&mut self) {
+        self.sort.reverse();
+        self.apply_sort();
+    }
+
+    /// Re-sorts all assets, search results stay ordered by their score first
+    fn apply_sort(&mut self) {
+        let sort = self.sort;
+        self.items.sort_by(|a, b| sort.compare(a, b));
+        self.filtered_items.sort_by(|a, b| sort.compare(a, b));
+        self.version += 1;
+
+        if !self.matches.is_empty() {
+            let matches = &self.matches;
+            self.filtered_items.sort_by_key(|asset| Reverse(matches[&asset.filename].score));
         }
     }
 
+    fn select(&mut self, nth: usize) {
+        self.state.select(Some(nth));
+        self.selected_assets = self.filtered_items.get(nth).cloned();
+    }
+
+    fn selected(&self) -> Option<&Asset> {
+        self.state.selected().and_then(|nth| self.filtered_items.get(nth))
+    }
+
+    /// Marks the asset as (not) fetched, both in the full and the filtered list
+    fn set_fetched(&mut self, filename: &str, fetched: bool) {
+        for asset in self.items.iter_mut().chain(self.filtered_items.iter_mut()) {
+            if asset.filename == filename {
+                asset.already_fetched = fetched;
+            }
+        }
+        self.version += 1;
+    }
+
+    /// (Un-)marks the currently selected asset
+    fn toggle_mark(&mut self) {
+        let Some(asset) = self.state.selected().and_then(|nth| self.filtered_items.get(nth)) else {
+            return;
+        };
+
+        if !self.marked.remove(&asset.filename) {
+            self.marked.insert(asset.filename.clone());
+        }
+    }
+
+    /// Marks every asset of the current filter results, or un-marks them if all of them are marked already
+    fn toggle_mark_all(&mut self) {
+        let filenames: Vec<String> = self.filtered_items.iter().map(|asset| asset.filename.clone()).collect();
+        self.toggle_mark_many(&filenames);
+    }
+
+    /// Marks all given assets, or un-marks them if all of them are marked already
+    fn toggle_mark_many(&mut self, filenames: &[String]) {
+        let all_marked = filenames.iter().all(|filename| self.marked.contains(filename));
+
+        for filename in filenames {
+            match all_marked {
+                true => self.marked.remove(filename),
+                false => self.marked.insert(filename.clone()),
+            };
+        }
+    }
+
+    /// Footer text showing how many assets are marked and how large they are in total
+    fn marked_summary(&self) -> String {
+        if self.marked.is_empty() {
+            return String::new();
+        }
+
+        let size: i64 = self
+            .items
+            .iter()
+            .filter(|asset| self.marked.contains(&asset.filename))
+            .map(|asset| asset.size)
+            .sum();
+
+        format!(
+            " {} marked ({}) - [CTRL+D] to fetch ",
+            self.marked.len(),
+            bytes_to_human_readable(size)
+        )
+    }
+
     fn next(&mut self) {
+        if self.filtered_items.is_empty() {
+            return;
+        }
+
         match self.state.selected() {
             Some(nth) => {
                 let to_select = if nth >= self.filtered_items.len() - 1 { 0 } else { nth + 1 };
@@ -273,6 +1504,10 @@ impl AssetList {
     }
 
     fn previous(&mut self) {
+        if self.filtered_items.is_empty() {
+            return;
+        }
+
         match self.state.selected() {
             Some(nth) => {
                 let to_select = if nth == 0 { self.filtered_items.len() - 1 } else { nth - 1 };
@@ -294,32 +1529,147 @@ impl AssetList {
         self.items.len()
     }
 
-    fn filter_and_sort(&mut self, query: &str) {
-        if query.is_empty() {
-            self.filtered_items = self.items.clone();
-            return;
+    fn filter_and_sort(&mut self, input: &str) -> Result<(), QueryError> {
+        let query = Query::parse(input)?;
+        self.version += 1;
+        let candidates = self.items.iter().filter(|asset| query.matches(asset));
+
+        if query.text.is_empty() {
+            self.filtered_items = candidates.cloned().collect();
+            self.matches.clear();
+            return Ok(());
         }
 
+        self.matches = candidates
+            .map(|asset| (asset.filename.clone(), fuzzy_match(&query.text, &asset.filename)))
+            .filter(|(_, matched)| matched.score >= MIN_SCORE)
+            .collect();
+
+        let matches = &self.matches;
         self.filtered_items = self
             .items
             .iter()
-            .filter(|asset| ratio(query, &asset.filename) >= 18)
+            .filter(|asset| matches.contains_key(&asset.filename))
             .cloned()
             .collect();
 
-        self.filtered_items
-            .sort_by_cached_key(|asset| Reverse(ratio(query, &asset.filename)));
+        self.filtered_items.sort_by_key(|asset| Reverse(matches[&asset.filename].score));
+
+        Ok(())
     }
 }
 
 impl Asset {
-    fn to_list_item(&self, _index: usize) -> ListItem {
-        let line = match self.already_fetched {
-            true => format!("{} [{}]  ✔", self.filename, bytes_to_human_readable(self.size)),
-            false => format!("{} [{}] ", self.filename, bytes_to_human_readable(self.size)),
+    fn to_list_item(&self, matched: Option<&FuzzyMatch>, marked: bool) -> ListItem<'_> {
+        let mut spans = match marked {
+            true => vec![Span::styled("● ", Style::default().magenta())],
+            false => vec![Span::raw("  ")],
         };
 
-        ListItem::new(line)
+        match matched {
+            Some(matched) => spans.extend(highlight_matches(&self.filename, matched)),
+            None => spans.push(Span::raw(self.filename.as_str())),
+        }
+
+        spans.push(Span::raw(format!(" [{}] ", bytes_to_human_readable(self.size))));
+        if self.already_fetched {
+            spans.push(Span::raw(" ✔"));
+        }
+        if self.rejected.is_some() {
+            spans.push(Span::styled(" ⚠ unsafe path", Style::default().red()));
+        }
+
+        ListItem::new(Line::from(spans))
+    }
+}
+
+impl TreeRow {
+    fn to_list_item(&self, marked: &HashSet<String>) -> ListItem<'_> {
+        match self {
+            TreeRow::Dir { depth, summary } => {
+                let icon = if summary.expanded { "▾" } else { "▸" };
+                let fetched = match summary.fetched == summary.assets {
+                    true => Span::styled(format!(" ({}/{}) ✔", summary.fetched, summary.assets), Style::default().green()),
+                    false => Span::raw(format!(" ({}/{})", summary.fetched, summary.assets)),
+                };
+
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("{}{icon} ", "  ".repeat(*depth))),
+                    Span::styled(format!("{}/", summary.name), Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(format!(" [{}]", bytes_to_human_readable(summary.size))),
+                    fetched,
+                ]))
+            }
+            TreeRow::File { depth, name, asset } => {
+                let marker = match marked.contains(&asset.filename) {
+                    true => Span::styled("● ", Style::default().magenta()),
+                    false => Span::raw("  "),
+                };
+
+                let mut spans = vec![
+                    Span::raw("  ".repeat(*depth)),
+                    marker,
+                    Span::raw(name.as_str()),
+                    Span::raw(format!(" [{}] ", bytes_to_human_readable(asset.size))),
+                ];
+                if asset.already_fetched {
+                    spans.push(Span::raw(" ✔"));
+                }
+                if asset.rejected.is_some() {
+                    spans.push(Span::styled(" ⚠ unsafe path", Style::default().red()));
+                }
+
+                ListItem::new(Line::from(spans))
+            }
+        }
+    }
+}
+
+/// Block surrounding the search box, showing the error of an invalid query in its title
+fn search_block(error: Option<&QueryError>) -> Block<'static> {
+    let block = Block::new().border_type(BorderType::Rounded).borders(Borders::all());
+
+    match error {
+        Some(e) => block.title(Line::from(format!(" {e} ")).red()).border_style(Style::default().red()),
+        None => block,
+    }
+}
+
+/// Splits `text` into spans, styling every character that was hit by the fuzzy matcher
+fn highlight_matches<'a>(text: &'a str, matched: &FuzzyMatch) -> Vec<Span<'a>> {
+    let highlight = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+    let mut spans = Vec::new();
+    let mut run_start = 0;
+    let mut run_matched = false;
+
+    for (i, _) in text.char_indices() {
+        let is_match = matched.is_match(i);
+        if is_match != run_matched && i > run_start {
+            spans.push(match run_matched {
+                true => Span::styled(&text[run_start..i], highlight),
+                false => Span::raw(&text[run_start..i]),
+            });
+            run_start = i;
+        }
+        run_matched = is_match;
+    }
+
+    if run_start < text.len() {
+        spans.push(match run_matched {
+            true => Span::styled(&text[run_start..], highlight),
+            false => Span::raw(&text[run_start..]),
+        });
+    }
+
+    spans
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m", secs / 3600, (secs % 3600) / 60),
     }
 }
 
@@ -0,0 +1,395 @@
+use crate::{
+    parser::parser::Asset,
+    sanitize::asset_path,
+    util::assets_dir,
+    verify::{self, crc32, Verification},
+};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt,
+    fs::{self, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+const MANIFEST_FILE: &str = "manifest.jsonl";
+
+/// Size and CRC last recorded for every file of a revision
+type Recorded = HashMap<String, (u64, u32)>;
+
+/// The recorded files of every revision. Loaded on first use and held while appending, so the lines of concurrently
+/// finishing jobs are not interleaved.
+static RECORDED: Mutex<Option<HashMap<String, Recorded>>> = Mutex::new(None);
+
+/// How a file got into the revision folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    Download,
+    /// Linked from an earlier revision or the object store
+    Link,
+    /// Found verified already, e.g. by a mirror, without knowing how it got there
+    Existing,
+}
+
+/// One completed download. Every revision folder has an append-only log of them, `assets/<revision>/manifest.jsonl`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub filename: String,
+    pub size: u64,
+    pub crc: u32,
+    /// Result of comparing the file against the file list right after it was written
+    pub verification: Verification,
+    pub url: String,
+    pub source: Source,
+    pub fetched_at: String,
+}
+
+impl ManifestEntry {
+    /// Entry for the file at `path`, which is hashed to compare it against the file list
+    pub fn hash(asset: &Asset, path: &Path, url: String, source: Source) -> io::Result<Self> {
+        let size = path.metadata()?.len();
+        let crc = crc32(path)?;
+        Ok(Self::new(asset, url, source, size, crc, verify::compare(asset, size, crc)))
+    }
+
+    /// Entry for a file which was verified already
+    pub fn verified(asset: &Asset, url: String, source: Source) -> Self {
+        Self::new(asset, url, source, asset.size as u64, asset.crc as u32, Verification::Verified)
+    }
+
+    fn new(asset: &Asset, url: String, source: Source, size: u64, crc: u32, verification: Verification) -> Self {
+        Self {
+            filename: asset.filename.clone(),
+            size,
+            crc,
+            verification,
+            url,
+            source,
+            fetched_at: Local::now().to_rfc3339(),
+        }
+    }
+}
+
+fn manifest_path(revision: &str) -> PathBuf {
+    assets_dir(revision).join(MANIFEST_FILE)
+}
+
+pub fn append(revision: &str, entry: &ManifestEntry) -> io::Result<()> {
+    append_if(revision, entry, |_| true)
+}
+
+/// Appends `entry` unless the manifest already records the same size and CRC for its file
+pub fn append_if_new(revision: &str, entry: &ManifestEntry) -> io::Result<()> {
+    append_if(revision, entry, |recorded| recorded != Some(&(entry.size, entry.crc)))
+}
+
+fn append_if(revision: &str, entry: &ManifestEntry, condition: impl FnOnce(Option<&(u64, u32)>) -> bool) -> io::Result<()> {
+    let mut recorded = RECORDED.lock().unwrap_or_else(|e| e.into_inner());
+    let recorded = match recorded.get_or_insert_with(HashMap::new).entry(revision.to_string()) {
+        Entry::Occupied(occupied) => occupied.into_mut(),
+        Entry::Vacant(vacant) => vacant.insert(
+            load(revision)?
+                .into_values()
+                .map(|entry| (entry.filename, (entry.size, entry.crc)))
+                .collect(),
+        ),
+    };
+    if !condition(recorded.get(&entry.filename)) {
+        return Ok(());
+    }
+
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+
+    let path = manifest_path(revision);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())?;
+    recorded.insert(entry.filename.clone(), (entry.size, entry.crc));
+    Ok(())
+}
+
+/// The most recent entry of every file in the manifest of `revision`
+pub fn load(revision: &str) -> io::Result<HashMap<String, ManifestEntry>> {
+    let file = match fs::File::open(manifest_path(revision)) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut entries = HashMap::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str::<ManifestEntry>(&line) {
+            Ok(entry) => {
+                entries.insert(entry.filename.clone(), entry);
+            }
+            Err(e) => log::warn!("Ignoring line {} of the manifest of {revision}: {e}", number + 1),
+        }
+    }
+    Ok(entries)
+}
+
+/// Why a file failed the audit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// The local file does not match the file list
+    Mismatch(Verification),
+    /// The file matches the file list, but the manifest has no record of downloading it
+    Unrecorded,
+    /// The manifest recorded content other than what is on disk now, the file was replaced after the download
+    Changed {
+        recorded: u32,
+        actual: u32,
+    },
+    /// Recorded in the manifest, but deleted since
+    Deleted,
+    /// Recorded in the manifest, but not part of the file list
+    NotInFileList,
+    Unreadable(String),
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::Mismatch(verification) => write!(f, "{verification}"),
+            Finding::Unrecorded => write!(f, "not recorded in the manifest"),
+            Finding::Changed { recorded, actual } => write!(f, "CRC is {actual:08x}, but the manifest recorded {recorded:08x}"),
+            Finding::Deleted => write!(f, "recorded in the manifest, but missing"),
+            Finding::NotInFileList => write!(f, "recorded in the manifest, but not in the file list"),
+            Finding::Unreadable(e) => write!(f, "failed to read it: {e}"),
+        }
+    }
+}
+
+/// Outcome of re-checking a revision folder
+#[derive(Debug, Clone, Default)]
+pub struct AuditReport {
+    pub revision: String,
+    pub total: usize,
+    pub verified: usize,
+    /// Assets which were never downloaded, which is fine for a partial mirror
+    pub not_downloaded: usize,
+    /// Filename and finding of every file which failed
+    pub failed: Vec<(String, Finding)>,
+}
+
+impl AuditReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "Checked {} assets of {}: {} verified, {} not downloaded, {} failed",
+            self.total,
+            self.revision,
+            self.verified,
+            self.not_downloaded,
+            self.failed.len()
+        )
+    }
+}
+
+/// Size and CRC of the file at `path`, `None` if there is none
+fn read(path: &Path) -> io::Result<Option<(u64, u32)>> {
+    let metadata = match path.metadata() {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    Ok(Some((metadata.len(), crc32(path)?)))
+}
+
+/// Re-checks every local file of `revision` against both its manifest and the file list. `progress` is called with the number
+/// of assets checked so far.
+pub fn audit(revision: &str, assets: &[Asset], mut progress: impl FnMut(usize)) -> io::Result<AuditReport> {
+    let mut manifest = load(revision)?;
+    let dir = assets_dir(revision);
+    let mut report = AuditReport {
+        revision: revision.to_string(),
+        total: assets.len(),
+        ..Default::default()
+    };
+
+    for (checked, asset) in assets.iter().enumerate() {
+        progress(checked);
+        let recorded = manifest.remove(&asset.filename);
+        // Unsafe paths are never downloaded
+        let Ok(path) = asset_path(&asset.filename) else {
+            report.not_downloaded += 1;
+            continue;
+        };
+
+        let finding = match (read(&dir.join(path)), recorded) {
+            (Ok(None), None) => {
+                report.not_downloaded += 1;
+                continue;
+            }
+            (Ok(None), Some(_)) => Finding::Deleted,
+            (Ok(Some((size, crc))), recorded) => match (verify::compare(asset, size, crc), recorded) {
+                (_, Some(entry)) if (entry.size, entry.crc) != (size, crc) => Finding::Changed {
+                    recorded: entry.crc,
+                    actual: crc,
+                },
+                (Verification::Verified, None) => Finding::Unrecorded,
+                (Verification::Verified, Some(_)) => {
+                    report.verified += 1;
+                    continue;
+                }
+                (verification, _) => Finding::Mismatch(verification),
+            },
+            (Err(e), _) => Finding::Unreadable(e.to_string()),
+        };
+        report.failed.push((asset.filename.clone(), finding));
+    }
+    progress(assets.len());
+
+    let mut unknown: Vec<String> = manifest.into_keys().collect();
+    unknown.sort();
+    report
+        .failed
+        .extend(unknown.into_iter().map(|filename| (filename, Finding::NotInFileList)));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing::temp_working_dir;
+
+    fn asset(filename: &str, content: &[u8]) -> Asset {
+        Asset {
+            filename: filename.to_string(),
+            size: content.len() as i64,
+            crc: crc32fast::hash(content) as i64,
+            ..Default::default()
+        }
+    }
+
+    /// Writes `content` to the asset's file and records it in the manifest
+    fn download(revision: &str, asset: &Asset, content: &[u8]) {
+        let path = assets_dir(revision).join(asset_path(&asset.filename).unwrap());
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, content).unwrap();
+        append(
+            revision,
+            &ManifestEntry::hash(asset, &path, String::new(), Source::Download).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn reports_every_finding() {
+        let _dir = temp_working_dir();
+        let revision = "V_audit";
+        let dir = assets_dir(revision);
+
+        let verified = asset("Data/verified.wad", b"verified");
+        download(revision, &verified, b"verified");
+
+        let unrecorded = asset("Data/unrecorded.wad", b"unrecorded");
+        fs::create_dir_all(dir.join("Data")).unwrap();
+        fs::write(dir.join("Data/unrecorded.wad"), b"unrecorded").unwrap();
+
+        let changed = asset("Data/changed.wad", b"original");
+        download(revision, &changed, b"original");
+        fs::write(dir.join("Data/changed.wad"), b"replaced").unwrap();
+
+        let mismatch = asset("Data/mismatch.wad", b"expected");
+        download(revision, &mismatch, b"received");
+
+        let deleted = asset("Data/deleted.wad", b"deleted");
+        download(revision, &deleted, b"deleted");
+        fs::remove_file(dir.join("Data/deleted.wad")).unwrap();
+
+        let unreadable = asset("Data/folder.wad", b"file");
+        fs::create_dir_all(dir.join("Data/folder.wad")).unwrap();
+
+        download(revision, &asset("Data/removed.wad", b"removed"), b"removed");
+        let not_downloaded = asset("Data/not_downloaded.wad", b"not downloaded");
+
+        let assets = [verified, unrecorded, changed, mismatch, deleted, unreadable, not_downloaded];
+        let mut checked = Vec::new();
+        let report = audit(revision, &assets, |count| checked.push(count)).unwrap();
+
+        assert_eq!(checked, (0..=assets.len()).collect::<Vec<_>>());
+        assert_eq!((report.total, report.verified, report.not_downloaded), (7, 1, 1));
+        let findings: Vec<_> = report
+            .failed
+            .iter()
+            .map(|(filename, finding)| (filename.as_str(), finding))
+            .collect();
+        assert_eq!(
+            findings[..3],
+            [
+                ("Data/unrecorded.wad", &Finding::Unrecorded),
+                (
+                    "Data/changed.wad",
+                    &Finding::Changed {
+                        recorded: crc32fast::hash(b"original"),
+                        actual: crc32fast::hash(b"replaced"),
+                    }
+                ),
+                (
+                    "Data/mismatch.wad",
+                    &Finding::Mismatch(Verification::CrcMismatch {
+                        expected: crc32fast::hash(b"expected"),
+                        actual: crc32fast::hash(b"received"),
+                    })
+                ),
+            ]
+        );
+        assert_eq!(findings[3], ("Data/deleted.wad", &Finding::Deleted));
+        assert!(matches!(findings[4], ("Data/folder.wad", Finding::Unreadable(_))));
+        assert_eq!(findings[5], ("Data/removed.wad", &Finding::NotInFileList));
+        assert_eq!(findings.len(), 6);
+        assert_eq!(
+            report.summary(),
+            "Checked 7 assets of V_audit: 1 verified, 1 not downloaded, 6 failed"
+        );
+    }
+
+    #[test]
+    fn counts_unsafe_paths_as_not_downloaded() {
+        let _dir = temp_working_dir();
+        let revision = "V_audit_unsafe";
+
+        // Even a file where the name leads and a record of it do not count as downloaded
+        let traversal = asset("../evil.wad", b"evil");
+        fs::create_dir_all(assets_dir(revision)).unwrap();
+        fs::write(assets_dir(revision).join("../evil.wad"), b"evil").unwrap();
+        append(revision, &ManifestEntry::verified(&traversal, String::new(), Source::Download)).unwrap();
+        let folder = asset("Data/", b"");
+
+        let report = audit(revision, &[traversal, folder], |_| {}).unwrap();
+        assert_eq!((report.total, report.verified, report.not_downloaded), (2, 0, 2));
+        assert!(report.failed.is_empty());
+    }
+
+    #[test]
+    fn records_existing_files_once() {
+        let _dir = temp_working_dir();
+        let revision = "V_audit_existing";
+        let existing = asset("Data/existing.wad", b"existing");
+
+        let entry = ManifestEntry::verified(&existing, String::new(), Source::Existing);
+        append_if_new(revision, &entry).unwrap();
+        append_if_new(revision, &entry).unwrap();
+        let changed = ManifestEntry { crc: 1, ..entry.clone() };
+        append_if_new(revision, &changed).unwrap();
+
+        let lines = fs::read_to_string(manifest_path(revision)).unwrap();
+        assert_eq!(lines.lines().count(), 2);
+        assert_eq!(load(revision).unwrap()["Data/existing.wad"].crc, 1);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::{
+    bytes_to_human_readable,
+    errors::{DownloadError, SyncError},
+    format_duration, index_cache,
+    parser::parser::{Asset, AssetFetcher, FetchOutcome},
+    sanitize::asset_path,
+    util::assets_dir,
+};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use tokio::task::JoinSet;
+
+/// Downloaded assets of an earlier revision, used to sync a new revision without downloading unchanged files again
+#[derive(Debug, Clone)]
+pub struct Previous {
+    pub revision: String,
+    dir: PathBuf,
+    /// Size and CRC of every asset, keyed by filename
+    assets: HashMap<String, (i64, i64)>,
+}
+
+impl Previous {
+    /// The most recently cached revision before `current` whose assets were (at least partly) downloaded
+    pub fn find(current: &str) -> Result<Self, SyncError> {
+        let revision = index_cache::revisions()?
+            .into_iter()
+            .rev()
+            .find(|revision| revision != current && assets_dir(revision).is_dir())
+            .ok_or(SyncError::NoPreviousRevision)?;
+
+        Self::load(&revision)
+    }
+
+    pub fn load(revision: &str) -> Result<Self, SyncError> {
+        let (revision, xml_text) = index_cache::load(revision)?;
+        let mut fetcher = AssetFetcher::new(revision);
+        fetcher.parse_index(xml_text)?;
+
+        Ok(Self {
+            dir: assets_dir(&fetcher.revision),
+            assets: fetcher
+                .assets
+                .into_iter()
+                .map(|asset| (asset.filename, (asset.size, asset.crc)))
+                .collect(),
+            revision: fetcher.revision,
+        })
+    }
+
+    /// Local path of `asset` in the earlier revision, if its size and CRC did not change
+    pub fn unchanged(&self, asset: &Asset) -> Option<PathBuf> {
+        let (size, crc) = self.assets.get(&asset.filename)?;
+        let path = asset_path(&asset.filename).ok()?;
+        (*size == asset.size && *crc == asset.crc).then(|| self.dir.join(path))
+    }
+}
+
+/// Outcome of mirroring a revision, updated while the mirror is running
+#[derive(Debug, Clone, Default)]
+pub struct MirrorReport {
+    pub revision: String,
+    pub total: usize,
+    pub downloaded: usize,
+    /// Assets whose local file was verified already
+    pub skipped: usize,
+    /// Assets linked from the earlier revision when syncing
+    pub reused: usize,
+    pub bytes: u64,
+    /// Bytes which did not have to be downloaded thanks to the earlier revision
+    pub bytes_saved: u64,
+    /// Revision unchanged files were linked from
+    pub previous: Option<String>,
+    /// Filename and reason of every failed download
+    pub failed: Vec<(String, String)>,
+    pub elapsed: Duration,
+    pub finished: bool,
+}
+
+impl MirrorReport {
+    pub fn done(&self) -> usize {
+        self.downloaded + self.skipped + self.reused + self.failed.len()
+    }
+
+    /// Where unchanged files were linked from, if any were
+    pub fn linked_from(&self) -> Option<&str> {
+        match &self.previous {
+            Some(previous) => Some(previous),
+            None if self.reused > 0 => Some("the object store"),
+            None => None,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        let reused = match self.linked_from() {
+            Some(previous) => format!(
+                ", {} linked from {previous} ({} saved)",
+                self.reused,
+                bytes_to_human_readable(self.bytes_saved as i64)
+            ),
+            None => String::new(),
+        };
+
+        format!(
+            "Mirrored {} of {} assets of {} in {}: {} downloaded ({}), {} already verified{reused}, {} failed",
+            self.downloaded + self.skipped + self.reused,
+            self.total,
+            self.revision,
+            format_duration(self.elapsed),
+            self.downloaded,
+            bytes_to_human_readable(self.bytes as i64),
+            self.skipped,
+            self.failed.len()
+        )
+    }
+}
+
+/// Makes sure there is enough disk space to mirror `assets`, not counting the ones which can be linked from `previous`
+pub fn check_space(fetcher: &AssetFetcher, assets: &[Asset], previous: Option<&Previous>) -> Result<(), DownloadError> {
+    fetcher.check_space(
+        assets
+            .iter()
+            .filter(|asset| previous.is_none_or(|previous| previous.unchanged(asset).is_none())),
+    )
+}
+
+/// Downloads every given asset through the download queue, skipping the ones that were verified already.
+/// With a `previous` revision, unchanged assets are linked from it instead. `on_progress` is called after every asset.
+pub async fn mirror(
+    fetcher: &AssetFetcher,
+    assets: &[Asset],
+    previous: Option<&Previous>,
+    on_progress: impl Fn(&MirrorReport),
+) -> MirrorReport {
+    let started = Instant::now();
+    let mut report = MirrorReport {
+        revision: fetcher.revision.clone(),
+        total: assets.len(),
+        previous: previous.map(|previous| previous.revision.clone()),
+        ..Default::default()
+    };
+
+    match previous {
+        Some(previous) => log::info!("Syncing {} assets of {} from {}", assets.len(), fetcher.revision, previous.revision),
+        None => log::info!("Mirroring {} assets of {}", assets.len(), fetcher.revision),
+    }
+
+    let schedule = fetcher.schedule();
+    if let Some(schedule) = schedule.filter(|schedule| !schedule.is_open()) {
+        log::info!("Waiting for the download window {schedule} to mirror {}", fetcher.revision);
+    }
+
+    let mut jobs = JoinSet::new();
+    for asset in assets {
+        let filename = asset.filename.clone();
+        let mut job = fetcher.job(asset).scheduled(schedule);
+        if let Some(path) = previous.and_then(|previous| previous.unchanged(asset)) {
+            job = job.reuse(path);
+        }
+
+        // The job runs in a task of its own, so a panic fails only its asset and is reported under its name
+        jobs.spawn(async move { (filename, tokio::spawn(job.run(true)).await) });
+    }
+
+    while let Some(joined) = jobs.join_next().await {
+        let (filename, result) = joined.expect("a mirror task only waits for its job");
+        match result {
+            Ok(Ok(FetchOutcome::Downloaded(bytes))) => {
+                report.downloaded += 1;
+                report.bytes += bytes;
+            }
+            Ok(Ok(FetchOutcome::Skipped)) => report.skipped += 1,
+            Ok(Ok(FetchOutcome::Reused(bytes))) => {
+                report.reused += 1;
+                report.bytes_saved += bytes;
+            }
+            Ok(Err(e)) => report.failed.push((filename, e.to_string())),
+            Err(e) => report.failed.push((filename, e.to_string())),
+        }
+
+        report.elapsed = started.elapsed();
+        on_progress(&report);
+    }
+
+    report.failed.sort();
+    report.finished = true;
+    log::info!("{}", report.summary());
+    for (filename, reason) in &report.failed {
+        log::warn!("Failed to mirror {filename}: {reason}");
+    }
+
+    report
+}
@@ -1,2 +1,3 @@
+#[allow(clippy::module_inception)]
 pub mod parser;
 pub mod structs;
@@ -1,15 +1,39 @@
 #![allow(unused)]
 
 use super::structs::{Element, LatestFiles, RecordUnion};
-use crate::revision_checker::Revision;
-use reqwest::Client;
-use std::{collections::VecDeque, io, path::PathBuf, process::exit};
+use crate::{
+    download::DownloadTracker,
+    errors::{DownloadError, HttpError, IndexError, StoreError, UnsafePathError},
+    hooks::Hooks,
+    http, index_cache,
+    manifest::{self, ManifestEntry, Source},
+    queue::{Cancellation, DownloadQueue, Slot},
+    revision_checker::Revision,
+    sanitize::asset_path,
+    settings::Settings,
+    store::ObjectStore,
+    throttle::{self, Schedule},
+    util::assets_dir,
+    verify::{verify, Verification},
+};
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 use tokio::{
-    fs::{create_dir_all, File},
+    fs::{create_dir_all, File, OpenOptions},
     io::AsyncWriteExt,
 };
 
-#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+/// How often a job checks whether another job fetching the same file is done
+const DUPLICATE_POLL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Serialize)]
 pub struct Asset {
     pub filename: String,
     pub size: i64,
@@ -18,6 +42,11 @@ pub struct Asset {
     pub crc: i64,
     pub header_crc: i64,
     pub already_fetched: bool,
+    /// Name of the table inside the LatestFileList this asset was listed in
+    pub table: String,
+    /// Why the filename is unsafe to save to disk. Such assets are listed, but never fetched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rejected: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -26,57 +55,119 @@ pub struct AssetFetcher {
     pub url_prefix: String,
     pub assets: VecDeque<Asset>,
     pub revision: String,
+    pub downloads: DownloadTracker,
+    pub queue: DownloadQueue,
     save_path: PathBuf,
+    store: Option<ObjectStore>,
+    hooks: Arc<Hooks>,
+    schedule: Option<Schedule>,
 }
 
 impl AssetFetcher {
     pub fn new(revision: Revision) -> Self {
+        let downloads = DownloadTracker::default();
+
         Self {
             assets: VecDeque::new(),
             revision: revision.clone().revision,
             url_prefix: revision.url_prefix,
             list_file_url: revision.list_file_url,
-            save_path: PathBuf::from(format!("assets/{}/", revision.revision)),
+            queue: DownloadQueue::new(&revision.revision, downloads.clone()),
+            downloads,
+            save_path: assets_dir(&revision.revision),
+            store: None,
+            hooks: Arc::default(),
+            schedule: None,
+        }
+    }
+
+    /// Applies the storage backend, post-download hooks and mirror schedule of the settings
+    pub fn configure(&mut self, settings: &Settings) -> Result<(), StoreError> {
+        self.hooks = Arc::new(Hooks::new(&settings.hooks));
+        self.schedule = settings.downloads.schedule.as_ref().and_then(Schedule::new);
+        match ObjectStore::configured(&settings.storage) {
+            Some(store) => self.use_store(store),
+            None => Ok(()),
         }
     }
 
-    pub async fn load_index(&mut self) {
-        // Fetches the XML version of the LatestFileList, which will be used to retrieve all assets
+    /// Keeps every fetched asset in `store` as well, `assets/<revision>/` then only links to it.
+    /// Writes the manifest of the loaded file list, so call this after parsing it.
+    pub fn use_store(&mut self, store: ObjectStore) -> Result<(), StoreError> {
+        store.write_manifest(&self.revision, &self.assets)?;
+        self.store = Some(store);
+        Ok(())
+    }
+
+    pub async fn load_index(&mut self) -> Result<(), IndexError> {
+        let xml_text = self.download_index(|_, _| {}).await?;
+        self.parse_downloaded_index(xml_text)
+    }
+
+    /// Fetches the XML version of the LatestFileList, which will be used to retrieve all assets.
+    /// `on_progress` receives the bytes received so far and the total size, if the server sent one.
+    pub async fn download_index(&self, on_progress: impl Fn(u64, Option<u64>)) -> Result<String, IndexError> {
         let xml_url = self.list_file_url.replace("LatestFileList.bin", "LatestFileList.xml");
-        if let Err(e) = self.fetch_xml(&xml_url).await {
-            panic!("Error fetching LatestFileList.bin: {e}")
+        let mut response = http::client().get(&xml_url).await?;
+        let total = response.content_length();
+
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+            on_progress(body.len() as u64, total);
         }
+
+        Ok(String::from_utf8_lossy(&body).to_string())
     }
 
-    async fn fetch_xml(&mut self, url: &str) -> io::Result<()> {
-        let response = Self::request_file(url).await.unwrap();
-        let xml_text = response.text().await.unwrap_or_default();
+    /// Parses a file list fetched by `download_index` and caches it once it parsed,
+    /// so a truncated or broken download never replaces the cached list.
+    pub fn parse_downloaded_index(&mut self, xml_text: String) -> Result<(), IndexError> {
+        self.parse_index(xml_text.clone())?;
+        if let Err(e) = index_cache::save(&self.to_revision(), &xml_text) {
+            log::warn!("Failed to cache the file list of {}: {e}", self.revision);
+        }
 
-        self.parse_and_store_elements(xml_text)?;
         Ok(())
     }
 
-    fn parse_and_store_elements(&mut self, xml_text: String) -> io::Result<()> {
+    pub fn parse_index(&mut self, xml_text: String) -> Result<(), IndexError> {
         let config = quickxml_to_serde::Config::new_with_defaults();
-        let json = quickxml_to_serde::xml_string_to_json(xml_text, &config).unwrap().to_string();
-        let parsed: LatestFiles = serde_json::from_str(&json).unwrap();
+        let json = quickxml_to_serde::xml_string_to_json(xml_text, &config)
+            .map_err(|e| IndexError::Xml(e.to_string()))?
+            .to_string();
+        let parsed: LatestFiles = serde_json::from_str(&json)?;
 
-        for (_, v) in parsed.latest_file_list {
+        for (table, v) in parsed.latest_file_list {
             match v.record {
-                RecordUnion::PurpleRecord(purple_record) => self.add_file_to_list(&purple_record),
+                RecordUnion::PurpleRecord(purple_record) => self.add_file_to_list(&purple_record, &table),
                 RecordUnion::RecordElementArray(records) => {
                     for r in records {
-                        self.add_file_to_list(&r);
+                        self.add_file_to_list(&r, &table);
                     }
                 }
             }
         }
+        self.reject_aliases();
 
         Ok(())
     }
 
-    fn add_file_to_list<T: Element>(&mut self, record: &T) {
+    pub fn to_revision(&self) -> Revision {
+        Revision {
+            list_file_url: self.list_file_url.clone(),
+            url_prefix: self.url_prefix.clone(),
+            revision: self.revision.clone(),
+        }
+    }
+
+    fn add_file_to_list<T: Element>(&mut self, record: &T, table: &str) {
         if let Some(src_file_name) = record.get_filename() {
+            let rejected = asset_path(&src_file_name).err().map(|e| e.to_string());
+            if let Some(reason) = &rejected {
+                log::warn!("Rejected {src_file_name} from the file list: {reason}");
+            }
+
             let file = Asset {
                 filename: src_file_name,
                 size: record.get_size(),
@@ -85,41 +176,592 @@ impl AssetFetcher {
                 crc: record.get_crc(),
                 header_crc: record.get_header_crc(),
                 already_fetched: false,
+                table: table.to_string(),
+                rejected,
             };
 
             self.assets.push_back(file);
         }
     }
 
-    async fn request_file(url: &str) -> Result<reqwest::Response, reqwest::Error> {
-        let client = Client::new();
-        client.get(url).header("User-Agent", "KingsIsle Patcher").send().await
+    /// Rejects filenames which differ from another one, like `a\b` and `a/b`, but are saved to the same file.
+    /// A filename already written like its path keeps the file, otherwise the smallest one does,
+    /// so the choice does not depend on the order of the tables.
+    fn reject_aliases(&mut self) {
+        let mut owners: HashMap<PathBuf, (bool, String)> = HashMap::new();
+        for asset in self.assets.iter().filter(|a| a.rejected.is_none()) {
+            if let Ok(path) = asset_path(&asset.filename) {
+                let normalized = path.iter().map(|s| s.to_string_lossy()).collect::<Vec<_>>().join("/");
+                let candidate = (asset.filename != normalized, asset.filename.clone());
+                let owner = owners.entry(path).or_insert_with(|| candidate.clone());
+                if candidate < *owner {
+                    *owner = candidate;
+                }
+            }
+        }
+
+        for asset in self.assets.iter_mut().filter(|a| a.rejected.is_none()) {
+            let Some((_, owner)) = asset_path(&asset.filename).ok().and_then(|path| owners.get(&path)) else {
+                continue;
+            };
+            if *owner != asset.filename {
+                let reason = UnsafePathError::SameFileAs(owner.clone()).to_string();
+                log::warn!("Rejected {} from the file list: {reason}", asset.filename);
+                asset.rejected = Some(reason);
+            }
+        }
     }
 
-    async fn write_to_file_chunked(path: &PathBuf, mut response: reqwest::Response) -> io::Result<()> {
+    /// Writes the response of `url` to `<path>.part`, appending to it if the response continues from `resume_from`.
+    /// If receiving the body fails, what was written is kept to resume from.
+    async fn write_to_file_chunked(
+        path: &PathBuf,
+        url: &str,
+        mut response: reqwest::Response,
+        downloads: &DownloadTracker,
+        filename: &str,
+        resume_from: u64,
+    ) -> Result<(), DownloadError> {
         if let Some(parent) = path.parent() {
             create_dir_all(parent).await?;
         }
 
-        let mut file = File::create(path).await?;
-        while let Some(chunk) = response.chunk().await.unwrap() {
-            file.write_all(&chunk).await?;
+        let partial = partial_path(path);
+        let written = async {
+            let mut file = match resume_from {
+                0 => File::create(&partial).await?,
+                _ => OpenOptions::new().append(true).open(&partial).await?,
+            };
+            loop {
+                let chunk = response.chunk().await.map_err(|source| HttpError::Request {
+                    url: url.to_string(),
+                    source,
+                });
+                let Some(chunk) = chunk? else {
+                    break;
+                };
+
+                throttle::consume(chunk.len() as u64).await;
+                file.write_all(&chunk).await?;
+                downloads.add_chunk(filename, chunk.len() as u64);
+            }
+            file.sync_all().await?;
+            Ok(())
         }
+        .await;
 
-        Ok(())
+        if let Err(e) = written {
+            if !matches!(e, DownloadError::Http(_)) {
+                let _ = tokio::fs::remove_file(&partial).await;
+            }
+            return Err(e);
+        }
+
+        // Renaming replaces the old file instead of writing into it, so a crash never leaves a broken file under the final name
+        // and an earlier revision the old file is hard-linked to stays untouched
+        Ok(tokio::fs::rename(&partial, path).await?)
+    }
+
+    /// Where `asset` is saved to once fetched, `None` if its filename was rejected
+    pub fn local_path(&self, asset: &Asset) -> Option<PathBuf> {
+        asset_path(&asset.filename).ok().map(|path| self.save_path.join(path))
+    }
+
+    /// Daily window mirrors are allowed to download in
+    pub fn schedule(&self) -> Option<Schedule> {
+        self.schedule
+    }
+
+    /// Makes sure the file system of the assets folder has room for a batch of downloads. Assets which are saved with the
+    /// right size already or can be linked from the object store do not count.
+    pub fn check_space<'a>(&self, assets: impl IntoIterator<Item = &'a Asset>) -> Result<(), DownloadError> {
+        let needed: u64 = assets
+            .into_iter()
+            .filter(|asset| asset.rejected.is_none())
+            .filter(|asset| !self.store.as_ref().is_some_and(|store| store.contains(asset)))
+            .filter(|asset| {
+                self.local_path(asset)
+                    .and_then(|path| path.metadata().ok())
+                    .is_none_or(|metadata| metadata.len() != asset.size as u64)
+            })
+            .map(|asset| asset.size as u64)
+            .sum();
+        if needed == 0 {
+            return Ok(());
+        }
+
+        // The folder of a new revision does not exist yet, but lives on the same file system as its parent
+        let existing = self.save_path.ancestors().find(|dir| dir.is_dir()).unwrap_or(Path::new("."));
+        let available = fs4::available_space(existing)?;
+
+        match needed > available {
+            true => Err(DownloadError::InsufficientSpace { needed, available }),
+            false => Ok(()),
+        }
+    }
+
+    /// Queues `asset` for download, the returned job has to be run (or spawned) to actually fetch it.
+    /// If another job is fetching the asset already, this one waits for it to end first.
+    pub fn job(&self, asset: &Asset) -> DownloadJob {
+        let owner = match asset.rejected {
+            Some(_) => None,
+            None => self.queue.claim(&asset.filename),
+        };
+        // A duplicate must not reset the progress of the running download
+        if owner.is_some() || asset.rejected.is_some() {
+            self.downloads.queue(&asset.filename, asset.size as u64);
+        }
+
+        DownloadJob {
+            asset: asset.clone(),
+            url: format!("{}/{}", self.url_prefix, asset.filename),
+            // Rejected assets fail before their path is used
+            path: self.local_path(asset).unwrap_or_default(),
+            downloads: self.downloads.clone(),
+            queue: self.queue.clone(),
+            reuse: self
+                .store
+                .as_ref()
+                .filter(|store| store.contains(asset))
+                .map(|store| store.object_path(asset)),
+            store: self.store.clone(),
+            hooks: self.hooks.clone(),
+            revision: self.revision.clone(),
+            owner,
+            cancellations: self.queue.cancellations(&asset.filename),
+            schedule: None,
+        }
     }
 
-    pub fn fetch_asset(&self, asset: &Asset) {
-        let url = format!("{}/{}", self.url_prefix, asset.filename);
-        let save_path = self.save_path.clone().join(asset.filename.clone());
+    /// Starts fetching `asset` in the background. Returns `false` if it is queued or downloading already.
+    pub fn fetch_asset(&self, asset: &Asset) -> bool {
+        if self.queue.is_active(&asset.filename) {
+            return false;
+        }
+
+        tokio::spawn(self.job(asset).run(false));
+        true
+    }
 
-        tokio::spawn(async move {
-            if let Ok(res) = Self::request_file(&url).await {
-                if let Err(e) = Self::write_to_file_chunked(&save_path, res).await {
-                    eprintln!("Error: {e}");
-                    exit(e.raw_os_error().unwrap_or(1));
+    /// Cancels the download of `asset`, whether it is queued or running. Returns `false` if it is neither.
+    pub fn cancel(&self, asset: &Asset) -> bool {
+        match self.queue.cancel(&asset.filename) {
+            Cancellation::NotQueued => false,
+            // The job deletes what it wrote once it stopped
+            Cancellation::Stopping => true,
+            Cancellation::Removed => {
+                // A download resumed from the last session may have a partial file already
+                if let Some(path) = self.local_path(asset) {
+                    let _ = std::fs::remove_file(partial_path(&path));
                 }
+                true
             }
+        }
+    }
+}
+
+/// Everything needed to download a single asset. Unlike the `AssetFetcher` with its whole file list, it is cheap to move into a task.
+#[derive(Debug)]
+pub struct DownloadJob {
+    asset: Asset,
+    url: String,
+    path: PathBuf,
+    downloads: DownloadTracker,
+    queue: DownloadQueue,
+    /// Identical file of an earlier revision or the object store, linked instead of downloading the asset again
+    reuse: Option<PathBuf>,
+    store: Option<ObjectStore>,
+    hooks: Arc<Hooks>,
+    revision: String,
+    /// Its id in the download queue, `None` while another job is fetching the same file
+    owner: Option<u64>,
+    /// How often the file's download was cancelled before this job was created
+    cancellations: u64,
+    /// Window the transfer has to start in, only set for mirrors
+    schedule: Option<Schedule>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchOutcome {
+    /// Number of bytes written
+    Downloaded(u64),
+    /// The local file was verified already
+    Skipped,
+    /// Hard-linked (or copied) from an earlier revision or the object store, number of bytes not downloaded
+    Reused(u64),
+}
+
+impl DownloadJob {
+    /// Links `path` instead of downloading the asset, as long as it still matches the file list
+    pub fn reuse(mut self, path: PathBuf) -> Self {
+        self.reuse = Some(path);
+        self
+    }
+
+    /// Only transfers while `schedule` is open. A transfer still running when it closes pauses until it opens again.
+    pub fn scheduled(mut self, schedule: Option<Schedule>) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Waits for a free slot in the download queue and fetches the asset.
+    /// With `skip_verified`, the local file is checked first and kept if it matches the file list.
+    pub async fn run(mut self, skip_verified: bool) -> Result<FetchOutcome, DownloadError> {
+        let filename = self.asset.filename.as_str();
+        if let Some(reason) = &self.asset.rejected {
+            let e = DownloadError::UnsafePath(reason.clone());
+            log::error!("Failed to fetch {filename}: {e}");
+            self.downloads.fail(filename, &e);
+            return Err(e);
+        }
+
+        // Writing the same file twice at once would mix up both downloads. Once the other job is done,
+        // a mirror usually finds the file verified.
+        while self.owner.is_none() {
+            tokio::time::sleep(DUPLICATE_POLL).await;
+            // Cancelling the other job's download cancels this one as well, instead of starting it over
+            if self.queue.cancellations(filename) != self.cancellations {
+                log::info!("Cancelled {filename}");
+                return Err(DownloadError::Cancelled);
+            }
+            self.owner = self.queue.claim(filename);
+            if self.owner.is_some() {
+                self.downloads.queue(filename, self.asset.size as u64);
+            }
+        }
+
+        let Some(mut slot) = self.wait_for_slot().await else {
+            log::info!("Cancelled {filename}");
+            self.downloads.remove(filename);
+            return Err(DownloadError::Cancelled);
+        };
+
+        if skip_verified {
+            let (path, asset) = (self.path.clone(), self.asset.clone());
+            let verification = tokio::task::spawn_blocking(move || verify(&path, &asset))
+                .await
+                .map_err(io::Error::other)?;
+            if verification.is_ok_and(Verification::is_verified) {
+                self.record(Source::Existing).await;
+                self.add_to_store(true).await;
+                self.downloads.remove(filename);
+                slot.finish();
+                return Ok(FetchOutcome::Skipped);
+            }
+        }
+
+        if let Some(source) = self.reuse.clone() {
+            let (target, asset) = (self.path.clone(), self.asset.clone());
+            let reused = tokio::task::spawn_blocking(move || link_verified(&source, &target, &asset))
+                .await
+                .map_err(io::Error::other)?;
+
+            match reused {
+                Ok(true) => {
+                    self.record(Source::Link).await;
+                    self.add_to_store(true).await;
+                    self.downloads.remove(filename);
+                    slot.finish();
+                    return Ok(FetchOutcome::Reused(self.asset.size as u64));
+                }
+                Ok(false) => log::warn!("{filename} of the earlier revision does not match the file list anymore, downloading it"),
+                Err(e) => log::warn!("Failed to reuse {filename} of the earlier revision, downloading it: {e}"),
+            }
+        }
+
+        self.downloads.start(filename, self.asset.size as u64);
+        let mut started_from = None;
+        let downloaded = loop {
+            let closed = async {
+                match self.schedule {
+                    Some(schedule) => schedule.wait_until_closed().await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                downloaded = self.download(&mut started_from) => break downloaded,
+                _ = slot.cancelled() => break Err(DownloadError::Cancelled),
+                _ = closed => {}
+            }
+
+            // The `.part` file is kept, the transfer continues from it once the window opens again
+            log::info!("Pausing {filename} until the download window opens");
+            drop(slot);
+            self.downloads.queue(filename, self.asset.size as u64);
+            let Some(resumed) = self.wait_for_slot().await else {
+                let _ = tokio::fs::remove_file(partial_path(&self.path)).await;
+                log::info!("Cancelled {filename}");
+                self.downloads.remove(filename);
+                return Err(DownloadError::Cancelled);
+            };
+            slot = resumed;
+        };
+
+        match downloaded {
+            Ok(received) => {
+                log::info!("Fetched {filename}");
+                let verification = self.record(Source::Download).await;
+                if let Some(verification) = verification.filter(|verification| !verification.is_verified()) {
+                    log::warn!("{filename} does not match the file list: {verification}");
+                }
+                self.downloads.finish(filename);
+
+                // Hooks may take a while, e.g. to extract a large archive, the next download can start meanwhile
+                slot.finish();
+                // They run before the file is linked into the object store, where editing it in place would change the content
+                // every revision shares. A file a hook may have changed is verified again.
+                let hooked = self.hooks.run(&self.path, &self.asset, &self.revision).await;
+                self.add_to_store(!hooked && verification.is_some_and(Verification::is_verified))
+                    .await;
+                Ok(FetchOutcome::Downloaded(received))
+            }
+            Err(DownloadError::Cancelled) => {
+                // The download stopped in the middle of writing its `.part` file
+                let _ = tokio::fs::remove_file(partial_path(&self.path)).await;
+                log::info!("Cancelled {filename}");
+                self.downloads.remove(filename);
+                slot.finish();
+                Err(DownloadError::Cancelled)
+            }
+            Err(e) => {
+                log::error!("Failed to fetch {filename}: {e}");
+                self.downloads.fail(filename, &e);
+                slot.fail(&e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Waits until the download window is open and it is the asset's turn. `None` if it was cancelled meanwhile.
+    async fn wait_for_slot(&self) -> Option<Slot> {
+        loop {
+            if let Some(schedule) = self.schedule {
+                schedule.wait_until_open().await;
+            }
+            let slot = self.queue.acquire(&self.asset.filename).await?;
+
+            // The window may have closed while waiting for the slot, which is left to other downloads meanwhile
+            if self.schedule.is_none_or(|schedule| schedule.is_open()) {
+                return Some(slot);
+            }
+        }
+    }
+
+    /// Appends the local file to the manifest of the revision. Downloads are hashed for it, other files were verified already.
+    /// Existing files are only recorded if the manifest does not know them yet, so mirroring again does not repeat them.
+    async fn record(&self, source: Source) -> Option<Verification> {
+        let (revision, asset, path, url) = (self.revision.clone(), self.asset.clone(), self.path.clone(), self.url.clone());
+        let result = tokio::task::spawn_blocking(move || {
+            let entry = match source {
+                Source::Download => ManifestEntry::hash(&asset, &path, url, source)?,
+                Source::Link | Source::Existing => ManifestEntry::verified(&asset, url, source),
+            };
+            match source {
+                Source::Existing => manifest::append_if_new(&revision, &entry)?,
+                Source::Download | Source::Link => manifest::append(&revision, &entry)?,
+            }
+            Ok(entry.verification)
+        })
+        .await
+        .map_err(io::Error::other)
+        .and_then(|result| result);
+
+        match result {
+            Ok(verification) => Some(verification),
+            Err(e) => {
+                log::warn!("Failed to record {} in the manifest: {e}", self.asset.filename);
+                None
+            }
+        }
+    }
+
+    /// Adds the local file to the object store, if one is used. Unless it was `verified` already, it is checked first,
+    /// so a broken download never ends up in the store.
+    async fn add_to_store(&self, verified: bool) {
+        let Some(store) = self.store.clone() else {
+            return;
+        };
+        if store.contains(&self.asset) {
+            return;
+        }
+
+        let (path, asset) = (self.path.clone(), self.asset.clone());
+        let result = tokio::task::spawn_blocking(move || {
+            if !verified {
+                let verification = verify(&path, &asset)?;
+                if !verification.is_verified() {
+                    return Err(io::Error::other(format!("the downloaded file is {verification}")));
+                }
+            }
+            store.insert(&path, &asset)
+        })
+        .await
+        .map_err(io::Error::other)
+        .and_then(|result| result);
+
+        if let Err(e) = result {
+            log::warn!("Failed to add {} to the object store: {e}", self.asset.filename);
+        }
+    }
+
+    /// Downloads the asset, continuing from the `.part` file an interrupted download left behind. A body which is cut off
+    /// by a transient error is resumed the same way, as often as requests are retried. Returns the bytes received since
+    /// `started_from`, the offset the first attempt started at.
+    async fn download(&self, started_from: &mut Option<u64>) -> Result<u64, DownloadError> {
+        let filename = self.asset.filename.as_str();
+        let mut attempt = 0;
+
+        loop {
+            let (response, resume_from) = self.request().await?;
+            let started_from = *started_from.get_or_insert(resume_from);
+
+            match AssetFetcher::write_to_file_chunked(&self.path, &self.url, response, &self.downloads, filename, resume_from).await {
+                Ok(()) => {
+                    let received = self.downloads.get(filename).map(|progress| progress.received).unwrap_or_default();
+                    return Ok(received.saturating_sub(started_from));
+                }
+                Err(DownloadError::Http(e)) if e.is_transient() => {
+                    let Some(delay) = http::client().retry_delay(attempt) else {
+                        return Err(e.into());
+                    };
+                    attempt += 1;
+                    log::warn!(
+                        "{e}, resuming in {:.1}s ({attempt}/{})",
+                        delay.as_secs_f64(),
+                        http::client().retries()
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Requests the asset from where its `.part` file ends. Returns the response and the offset it starts at.
+    async fn request(&self) -> Result<(reqwest::Response, u64), DownloadError> {
+        let filename = self.asset.filename.as_str();
+        let partial = tokio::fs::metadata(partial_path(&self.path)).await.map(|metadata| metadata.len());
+        let offset = partial.ok().filter(|&len| len < self.asset.size as u64).unwrap_or_default();
+
+        let response = match offset {
+            0 => http::client().get(&self.url).await?,
+            offset => http::client().get_from(&self.url, offset).await?,
+        };
+        let resume_from = match response.status() {
+            StatusCode::PARTIAL_CONTENT => offset,
+            _ => 0,
+        };
+
+        match (resume_from, response.content_length()) {
+            (0, Some(len)) => self.downloads.start(filename, len),
+            (0, None) => {}
+            (offset, len) => {
+                log::info!("Resuming {filename} at {}", crate::bytes_to_human_readable(offset as i64));
+                self.downloads.resume(filename, offset + len.unwrap_or_default(), offset);
+            }
+        }
+
+        Ok((response, resume_from))
+    }
+}
+
+impl Drop for DownloadJob {
+    fn drop(&mut self) {
+        if let Some(owner) = self.owner {
+            self.queue.unclaim(&self.asset.filename, owner);
+        }
+    }
+}
+
+/// Downloads are written to `<path>.part` and renamed once complete
+fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".part");
+    PathBuf::from(partial)
+}
+
+/// Hard-links `source` to `target` if it matches `asset`, falling back to a copy if linking is not possible
+/// (e.g. across file systems). Returns `false` if `source` does not match.
+fn link_verified(source: &Path, target: &Path, asset: &Asset) -> io::Result<bool> {
+    if !verify(source, asset)?.is_verified() {
+        return Ok(false);
+    }
+
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if target.exists() {
+        std::fs::remove_file(target)?;
+    }
+
+    if std::fs::hard_link(source, target).is_err() {
+        std::fs::copy(source, target)?;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(filename: &str) -> String {
+        format!(
+            r#"<RECORD><SrcFileName TYPE="STR">{filename}</SrcFileName><Size TYPE="UINT">5</Size><CRC TYPE="UINT">1</CRC><HeaderSize TYPE="UINT">0</HeaderSize><CompressedHeaderSize TYPE="UINT">0</CompressedHeaderSize><HeaderCRC TYPE="UINT">0</HeaderCRC></RECORD>"#
+        )
+    }
+
+    fn parse(tables: &[(&str, &[&str])]) -> AssetFetcher {
+        let tables: String = tables
+            .iter()
+            .map(|(table, filenames)| format!("<{table}>{}</{table}>", filenames.iter().map(|f| record(f)).collect::<String>()))
+            .collect();
+        let mut fetcher = AssetFetcher::new(Revision {
+            list_file_url: String::new(),
+            url_prefix: String::new(),
+            revision: "V_r1.0".to_string(),
         });
+        fetcher
+            .parse_index(format!(r#"<?xml version="1.0"?><LatestFileList>{tables}</LatestFileList>"#))
+            .unwrap();
+        fetcher
+    }
+
+    fn rejected(fetcher: &AssetFetcher, filename: &str) -> Vec<Option<String>> {
+        fetcher
+            .assets
+            .iter()
+            .filter(|a| a.filename == filename)
+            .map(|a| a.rejected.clone())
+            .collect()
+    }
+
+    #[test]
+    fn rejects_separator_variants_of_the_same_file() {
+        let fetcher = parse(&[
+            ("Data", &["Data\\GameData\\Root.wad", "Bin/readme.txt", "Bin\\.\\readme.txt"]),
+            ("Other", &["Data/GameData/Root.wad", "Data//GameData/Root.wad", "Bin/new.txt"]),
+        ]);
+
+        assert_eq!(rejected(&fetcher, "Data/GameData/Root.wad"), [None]);
+        let same_as_root = [Some("Saved to the same file as Data/GameData/Root.wad".to_string())];
+        assert_eq!(rejected(&fetcher, "Data\\GameData\\Root.wad"), same_as_root);
+        assert_eq!(rejected(&fetcher, "Data//GameData/Root.wad"), same_as_root);
+        assert_eq!(rejected(&fetcher, "Bin/readme.txt"), [None]);
+        assert_eq!(
+            rejected(&fetcher, "Bin\\.\\readme.txt"),
+            [Some("Saved to the same file as Bin/readme.txt".to_string())]
+        );
+        assert_eq!(rejected(&fetcher, "Bin/new.txt"), [None]);
+    }
+
+    #[test]
+    fn keeps_the_same_filename_in_several_tables() {
+        let fetcher = parse(&[
+            ("Data", &["Bin/readme.txt", "Data/"]),
+            ("Other", &["Bin/readme.txt", "Bin/new.txt"]),
+        ]);
+
+        assert_eq!(rejected(&fetcher, "Bin/readme.txt"), [None, None]);
+        assert_eq!(rejected(&fetcher, "Data/"), [Some("Names a folder, not a file".to_string())]);
     }
 }
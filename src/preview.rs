@@ -0,0 +1,619 @@
+use crate::{bytes_to_human_readable, format_duration, texture::Image};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Paragraph, Widget},
+};
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// Bytes read from the start of a file, enough for every header and a few screens of text
+const HEAD_SIZE: u64 = 64 * 1024;
+/// Bytes read from the end of Ogg files, to find the granule position of the last page
+const TAIL_SIZE: u64 = 64 * 1024;
+const MAX_TEXT_LINES: usize = 500;
+
+/// File type, detected from the first bytes of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Wad,
+    Dds,
+    Png,
+    Jpeg,
+    Bmp,
+    Gif,
+    Ogg,
+    Wav,
+    Mp3,
+    Xml,
+    Text,
+    Binary,
+}
+
+impl FileKind {
+    pub fn detect(head: &[u8]) -> Self {
+        match head {
+            [b'K', b'I', b'W', b'A', b'D', ..] => FileKind::Wad,
+            [b'D', b'D', b'S', b' ', ..] => FileKind::Dds,
+            [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', ..] => FileKind::Png,
+            [0xff, 0xd8, 0xff, ..] => FileKind::Jpeg,
+            [b'B', b'M', ..] if head.len() >= 30 => FileKind::Bmp,
+            [b'G', b'I', b'F', b'8', ..] => FileKind::Gif,
+            [b'O', b'g', b'g', b'S', ..] => FileKind::Ogg,
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => FileKind::Wav,
+            [b'I', b'D', b'3', ..] => FileKind::Mp3,
+            [0xff, second, ..] if second & 0xe0 == 0xe0 => FileKind::Mp3,
+            _ if is_text(head) => {
+                let text = String::from_utf8_lossy(head);
+                match text.trim_start_matches('\u{feff}').trim_start().starts_with('<') {
+                    true => FileKind::Xml,
+                    false => FileKind::Text,
+                }
+            }
+            _ => FileKind::Binary,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FileKind::Wad => "KIWAD archive",
+            FileKind::Dds => "DDS texture",
+            FileKind::Png => "PNG image",
+            FileKind::Jpeg => "JPEG image",
+            FileKind::Bmp => "Bitmap image",
+            FileKind::Gif => "GIF image",
+            FileKind::Ogg => "Ogg audio",
+            FileKind::Wav => "WAVE audio",
+            FileKind::Mp3 => "MP3 audio",
+            FileKind::Xml => "XML document",
+            FileKind::Text => "Text",
+            FileKind::Binary => "Binary",
+        }
+    }
+}
+
+/// Content preview of a downloaded asset, shown below its details
+#[derive(Debug, Clone)]
+pub struct Preview {
+    pub path: PathBuf,
+    pub kind: FileKind,
+    size: u64,
+    modified: Option<SystemTime>,
+    summary: Vec<(&'static str, String)>,
+    head: Vec<u8>,
+    /// Thumbnail of PNG and DDS textures
+    image: Option<Image>,
+}
+
+impl Preview {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let mut file = File::open(path)?;
+
+        let mut head = Vec::new();
+        (&mut file).take(HEAD_SIZE).read_to_end(&mut head)?;
+
+        let kind = FileKind::detect(&head);
+        let summary = match kind {
+            FileKind::Wad => wad_summary(&head),
+            FileKind::Dds => dds_summary(&head),
+            FileKind::Png => png_summary(&head),
+            FileKind::Jpeg => jpeg_summary(&head),
+            FileKind::Bmp => bmp_summary(&head),
+            FileKind::Gif => gif_summary(&head),
+            FileKind::Ogg => ogg_summary(&head, &mut file, metadata.len())?,
+            FileKind::Wav => wav_summary(&head),
+            FileKind::Mp3 => mp3_summary(&head, metadata.len()),
+            FileKind::Xml | FileKind::Text => text_summary(&head, metadata.len()),
+            FileKind::Binary => Vec::new(),
+        };
+
+        let mut preview = Self {
+            path: path.to_path_buf(),
+            kind,
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+            summary,
+            head,
+            image: None,
+        };
+
+        let image = match kind {
+            FileKind::Png => Some(Image::from_png(&fs::read(path)?)),
+            FileKind::Dds => Some(Image::from_dds(&fs::read(path)?)),
+            _ => None,
+        };
+        match image {
+            Some(Ok(image)) => preview.image = Some(image),
+            Some(Err(e)) => preview.summary.push(("Preview", e.to_string())),
+            None => {}
+        }
+
+        Ok(preview)
+    }
+
+    /// Whether the file was changed (or removed) since the preview was loaded
+    pub fn is_stale(&self) -> bool {
+        match fs::metadata(&self.path) {
+            Ok(metadata) => metadata.len() != self.size || metadata.modified().ok() != self.modified,
+            Err(_) => true,
+        }
+    }
+
+    fn summary_lines(&self) -> Vec<Line<'static>> {
+        let mut lines = vec![
+            summary_line("Type", self.kind.name().to_string()),
+            summary_line("File size", bytes_to_human_readable(self.size as i64)),
+        ];
+        lines.extend(self.summary.iter().map(|(label, value)| summary_line(label, value.clone())));
+        lines
+    }
+}
+
+impl Widget for &Preview {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let block = Block::new()
+            .border_type(BorderType::Rounded)
+            .borders(Borders::all())
+            .title(" Preview ");
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let summary = self.summary_lines();
+        let [summary_area, _, body_area] =
+            Layout::vertical([Constraint::Length(summary.len() as u16), Constraint::Length(1), Constraint::Min(0)]).areas(inner);
+        Paragraph::new(summary).render(summary_area, buf);
+
+        if let Some(image) = &self.image {
+            image.render(body_area, buf);
+            return;
+        }
+
+        let body = match self.kind {
+            FileKind::Xml => highlight_xml(&String::from_utf8_lossy(&self.head)),
+            FileKind::Text => plain_text(&String::from_utf8_lossy(&self.head)),
+            _ => hex_dump(&self.head, body_area.width, body_area.height),
+        };
+        Paragraph::new(body).render(body_area, buf);
+    }
+}
+
+fn summary_line(label: &str, value: String) -> Line<'static> {
+    Line::from(vec![
+        Span::styled(format!("{label}: "), Style::default().dark_gray()),
+        Span::raw(value),
+    ])
+}
+
+/// Valid UTF-8 (apart from a character cut off at the end) without NUL bytes
+fn is_text(head: &[u8]) -> bool {
+    if head.is_empty() || head.contains(&0) {
+        return false;
+    }
+
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none() && head.len() as u64 == HEAD_SIZE,
+    }
+}
+
+fn u16_le(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn u16_be(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn u32_le(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn u32_be(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn i64_le(bytes: &[u8], offset: usize) -> Option<i64> {
+    Some(i64::from_le_bytes(bytes.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+fn dimensions(width: impl std::fmt::Display, height: impl std::fmt::Display) -> (&'static str, String) {
+    ("Dimensions", format!("{width} × {height}"))
+}
+
+/// `KIWAD`, version, entry count and (since version 2) a flags byte
+fn wad_summary(head: &[u8]) -> Vec<(&'static str, String)> {
+    let (Some(version), Some(entries)) = (u32_le(head, 5), u32_le(head, 9)) else {
+        return Vec::new();
+    };
+
+    vec![("Version", version.to_string()), ("Entries", entries.to_string())]
+}
+
+/// Name of the pixel format of a DDS texture, e.g. `DXT5` or `RGBA32`
+fn dds_format(head: &[u8]) -> Option<String> {
+    const DDPF_ALPHAPIXELS: u32 = 0x1;
+    const DDPF_FOURCC: u32 = 0x4;
+
+    let flags = u32_le(head, 80)?;
+    if flags & DDPF_FOURCC != 0 {
+        return Some(String::from_utf8_lossy(head.get(84..88)?).trim_end_matches('\0').to_string());
+    }
+
+    let bits = u32_le(head, 88)?;
+    Some(match flags & DDPF_ALPHAPIXELS != 0 {
+        true => format!("RGBA{bits}"),
+        false => format!("RGB{bits}"),
+    })
+}
+
+fn dds_summary(head: &[u8]) -> Vec<(&'static str, String)> {
+    let (Some(height), Some(width)) = (u32_le(head, 12), u32_le(head, 16)) else {
+        return Vec::new();
+    };
+
+    let mut summary = vec![dimensions(width, height)];
+    if let Some(format) = dds_format(head) {
+        summary.push(("Format", format));
+    }
+    if let Some(mipmaps) = u32_le(head, 28).filter(|mipmaps| *mipmaps > 0) {
+        summary.push(("Mipmaps", mipmaps.to_string()));
+    }
+    summary
+}
+
+fn png_summary(head: &[u8]) -> Vec<(&'static str, String)> {
+    let (Some(width), Some(height), Some(depth), Some(color_type)) = (u32_be(head, 16), u32_be(head, 20), head.get(24), head.get(25))
+    else {
+        return Vec::new();
+    };
+
+    let color = match color_type {
+        0 => "Grayscale",
+        2 => "RGB",
+        3 => "Indexed",
+        4 => "Grayscale + alpha",
+        6 => "RGBA",
+        _ => "Unknown",
+    };
+
+    vec![dimensions(width, height), ("Colour", format!("{color}, {depth} bit"))]
+}
+
+/// Walks the JPEG markers up to the first start of frame
+fn jpeg_summary(head: &[u8]) -> Vec<(&'static str, String)> {
+    let mut offset = 2;
+    while let (Some(0xff), Some(marker), Some(length)) = (head.get(offset), head.get(offset + 1), u16_be(head, offset + 2)) {
+        let is_frame = matches!(marker, 0xc0..=0xcf) && !matches!(marker, 0xc4 | 0xc8 | 0xcc);
+        if is_frame {
+            let (Some(height), Some(width)) = (u16_be(head, offset + 5), u16_be(head, offset + 7)) else {
+                break;
+            };
+            return vec![dimensions(width, height)];
+        }
+
+        offset += 2 + length as usize;
+    }
+
+    Vec::new()
+}
+
+fn bmp_summary(head: &[u8]) -> Vec<(&'static str, String)> {
+    let (Some(width), Some(height), Some(bits)) = (u32_le(head, 18), u32_le(head, 22), u16_le(head, 28)) else {
+        return Vec::new();
+    };
+
+    // Negative heights mark top-down bitmaps
+    let height = (height as i32).unsigned_abs();
+    vec![dimensions(width, height), ("Colour", format!("{bits} bit"))]
+}
+
+fn gif_summary(head: &[u8]) -> Vec<(&'static str, String)> {
+    let (Some(width), Some(height)) = (u16_le(head, 6), u16_le(head, 8)) else {
+        return Vec::new();
+    };
+
+    vec![dimensions(width, height)]
+}
+
+fn duration(seconds: f64) -> (&'static str, String) {
+    ("Duration", format_duration(Duration::from_secs_f64(seconds.max(0.0))))
+}
+
+/// Reads the codec from the first page and the duration from the granule position of the last one
+fn ogg_summary(head: &[u8], file: &mut File, len: u64) -> io::Result<Vec<(&'static str, String)>> {
+    let Some(segments) = head.get(26) else {
+        return Ok(Vec::new());
+    };
+    let packet = &head[(27 + *segments as usize).min(head.len())..];
+
+    let (codec, channels, sample_rate) = if packet.starts_with(b"\x01vorbis") {
+        ("Vorbis", packet.get(11).copied(), u32_le(packet, 12))
+    } else if packet.starts_with(b"OpusHead") {
+        // Opus granule positions are always counted at 48 kHz
+        ("Opus", packet.get(9).copied(), Some(48000))
+    } else {
+        return Ok(vec![("Codec", "Unknown".to_string())]);
+    };
+
+    let mut summary = vec![("Codec", codec.to_string())];
+    if let Some(channels) = channels {
+        summary.push(("Channels", channels.to_string()));
+    }
+
+    let Some(sample_rate) = sample_rate.filter(|rate| *rate > 0) else {
+        return Ok(summary);
+    };
+    if codec == "Vorbis" {
+        summary.push(("Sample rate", format!("{sample_rate} Hz")));
+    }
+
+    let mut tail = Vec::new();
+    file.seek(SeekFrom::Start(len.saturating_sub(TAIL_SIZE)))?;
+    file.take(TAIL_SIZE).read_to_end(&mut tail)?;
+
+    let last_page = tail.windows(4).rposition(|window| window == b"OggS");
+    if let Some(granule) = last_page.and_then(|page| i64_le(&tail, page + 6)) {
+        summary.push(duration(granule as f64 / sample_rate as f64));
+    }
+
+    Ok(summary)
+}
+
+/// Walks the RIFF chunks for the format and the size of the sample data
+fn wav_summary(head: &[u8]) -> Vec<(&'static str, String)> {
+    let mut summary = Vec::new();
+    let mut byte_rate = None;
+    let mut offset = 12;
+
+    while let (Some(id), Some(size)) = (head.get(offset..offset + 4), u32_le(head, offset + 4)) {
+        let data = offset + 8;
+        match id {
+            b"fmt " => {
+                if let (Some(channels), Some(rate), Some(bytes), Some(bits)) = (
+                    u16_le(head, data + 2),
+                    u32_le(head, data + 4),
+                    u32_le(head, data + 8),
+                    u16_le(head, data + 14),
+                ) {
+                    summary.push(("Channels", channels.to_string()));
+                    summary.push(("Sample rate", format!("{rate} Hz, {bits} bit")));
+                    byte_rate = Some(bytes);
+                }
+            }
+            b"data" => {
+                if let Some(byte_rate) = byte_rate.filter(|rate| *rate > 0) {
+                    summary.push(duration(size as f64 / byte_rate as f64));
+                }
+                break;
+            }
+            _ => {}
+        }
+
+        // Chunks are padded to an even size
+        offset = data + size as usize + (size as usize & 1);
+    }
+
+    summary
+}
+
+/// Estimates the duration from the bitrate of the first frame, which is exact for constant bitrates only
+fn mp3_summary(head: &[u8], len: u64) -> Vec<(&'static str, String)> {
+    const BITRATES_V1: [u32; 16] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 0];
+    const BITRATES_V2: [u32; 16] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160, 0];
+    const SAMPLE_RATES: [u32; 4] = [44100, 48000, 32000, 0];
+
+    // ID3v2 tags store their size as four 7 bit integers
+    let tag_size = match head.starts_with(b"ID3") {
+        true => match head.get(6..10) {
+            Some(size) => 10 + size.iter().fold(0usize, |acc, byte| (acc << 7) | (*byte & 0x7f) as usize),
+            None => return Vec::new(),
+        },
+        false => 0,
+    };
+
+    let Some(header) = head.get(tag_size..tag_size + 4) else {
+        return Vec::new();
+    };
+    if header[0] != 0xff || header[1] & 0xe0 != 0xe0 {
+        return Vec::new();
+    }
+
+    let version_1 = header[1] & 0x18 == 0x18;
+    let bitrate = match version_1 {
+        true => BITRATES_V1[(header[2] >> 4) as usize],
+        false => BITRATES_V2[(header[2] >> 4) as usize],
+    };
+    let sample_rate = SAMPLE_RATES[((header[2] >> 2) & 0x3) as usize];
+    let channels = if header[3] >> 6 == 3 { 1 } else { 2 };
+
+    let mut summary = vec![("Channels", channels.to_string())];
+    if sample_rate > 0 {
+        summary.push(("Sample rate", format!("{sample_rate} Hz")));
+    }
+    if bitrate > 0 {
+        summary.push(("Bitrate", format!("{bitrate} kbit/s")));
+        summary.push(duration(len.saturating_sub(tag_size as u64) as f64 * 8.0 / (bitrate * 1000) as f64));
+    }
+
+    summary
+}
+
+fn text_summary(head: &[u8], len: u64) -> Vec<(&'static str, String)> {
+    let lines = String::from_utf8_lossy(head).lines().count();
+    match len > head.len() as u64 {
+        true => vec![(
+            "Lines",
+            format!("{lines}+ (first {} shown)", bytes_to_human_readable(head.len() as i64)),
+        )],
+        false => vec![("Lines", lines.to_string())],
+    }
+}
+
+/// Collects styled characters into lines, merging runs of the same style into one span
+#[derive(Default)]
+struct LineBuilder {
+    lines: Vec<Line<'static>>,
+    spans: Vec<Span<'static>>,
+    current: String,
+    style: Style,
+}
+
+impl LineBuilder {
+    fn push(&mut self, c: char, style: Style) {
+        match c {
+            '\n' => {
+                self.flush();
+                self.lines.push(Line::from(std::mem::take(&mut self.spans)));
+            }
+            '\r' => {}
+            _ => {
+                if style != self.style {
+                    self.flush();
+                    self.style = style;
+                }
+
+                match c {
+                    '\t' => self.current.push_str("    "),
+                    _ => self.current.push(c),
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.current.is_empty() {
+            self.spans.push(Span::styled(std::mem::take(&mut self.current), self.style));
+        }
+    }
+
+    fn finish(mut self) -> Vec<Line<'static>> {
+        self.flush();
+        if !self.spans.is_empty() {
+            self.lines.push(Line::from(self.spans));
+        }
+
+        self.lines.truncate(MAX_TEXT_LINES);
+        self.lines
+    }
+}
+
+fn plain_text(text: &str) -> Vec<Line<'static>> {
+    text.lines()
+        .take(MAX_TEXT_LINES)
+        .map(|line| Line::from(line.replace('\t', "    ")))
+        .collect()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum XmlState {
+    Text,
+    TagName,
+    Tag,
+    Value(char),
+    Comment,
+}
+
+/// Colours tags, attribute names, attribute values and comments
+fn highlight_xml(text: &str) -> Vec<Line<'static>> {
+    let tag = Style::default().cyan();
+    let attribute = Style::default().yellow();
+    let value = Style::default().green();
+    let comment = Style::default().dark_gray();
+
+    let chars: Vec<char> = text.trim_start_matches('\u{feff}').chars().collect();
+    let mut builder = LineBuilder::default();
+    let mut state = XmlState::Text;
+
+    for (i, &c) in chars.iter().enumerate() {
+        let style = match state {
+            XmlState::Text if c == '<' => {
+                state = match chars[i..].starts_with(&['<', '!', '-', '-']) {
+                    true => XmlState::Comment,
+                    false => XmlState::TagName,
+                };
+                match state {
+                    XmlState::Comment => comment,
+                    _ => tag,
+                }
+            }
+            XmlState::Text => Style::default(),
+            XmlState::TagName if c.is_whitespace() => {
+                state = XmlState::Tag;
+                Style::default()
+            }
+            XmlState::TagName => {
+                if c == '>' {
+                    state = XmlState::Text;
+                }
+                tag
+            }
+            XmlState::Tag => match c {
+                '"' | '\'' => {
+                    state = XmlState::Value(c);
+                    value
+                }
+                '>' => {
+                    state = XmlState::Text;
+                    tag
+                }
+                '/' | '?' => tag,
+                '=' => Style::default(),
+                _ => attribute,
+            },
+            XmlState::Value(quote) => {
+                if c == quote {
+                    state = XmlState::Tag;
+                }
+                value
+            }
+            XmlState::Comment => {
+                if c == '>' && i >= 2 && chars[i - 2..i] == ['-', '-'] {
+                    state = XmlState::Text;
+                }
+                comment
+            }
+        };
+
+        builder.push(c, style);
+    }
+
+    builder.finish()
+}
+
+/// Classic `offset  hex  ascii` dump, as many bytes per row as fit into `width`
+fn hex_dump(bytes: &[u8], width: u16, height: u16) -> Vec<Line<'static>> {
+    // Offset and separators take 10 columns, every byte 3 for its hex value and 1 for its character
+    let per_row = ((width.saturating_sub(10) / 4) as usize / 4 * 4).clamp(4, 16);
+
+    bytes
+        .chunks(per_row)
+        .take(height as usize)
+        .enumerate()
+        .map(|(row, chunk)| {
+            let hex: String = chunk.iter().map(|byte| format!("{byte:02x} ")).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|byte| match byte.is_ascii_graphic() || *byte == b' ' {
+                    true => *byte as char,
+                    false => '.',
+                })
+                .collect();
+
+            Line::from(vec![
+                Span::styled(format!("{:08x}  ", row * per_row), Style::default().dark_gray()),
+                Span::raw(format!("{hex:<width$}", width = per_row * 3)),
+                Span::styled(ascii, Style::default().cyan()),
+            ])
+        })
+        .collect()
+}
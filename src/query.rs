@@ -0,0 +1,306 @@
+use crate::{errors::QueryError, parser::parser::Asset};
+use regex::Regex;
+
+/// A single structured term of a search query, e.g. `ext:wad` or `size>10MB`.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    Extension(String),
+    Size(Comparison, i64),
+    Fetched(bool),
+    Table(String),
+    Crc(i64),
+    Regex(Regex),
+    /// Quoted, case-sensitive substring
+    Exact(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    GreaterOrEqual,
+    Greater,
+}
+
+/// Parsed content of the search box.
+///
+/// Structured filters narrow down the assets first, all remaining free-text terms are then used for fuzzy matching.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    pub filters: Vec<Filter>,
+    pub text: String,
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Self, QueryError> {
+        let mut query = Query::default();
+        let mut text = Vec::new();
+
+        for (token, quoted) in tokenize(input) {
+            if quoted {
+                query.filters.push(Filter::Exact(token));
+                continue;
+            }
+
+            match parse_filter(&token)? {
+                Some(filter) => query.filters.push(filter),
+                None => text.push(token),
+            }
+        }
+
+        query.text = text.join(" ");
+        Ok(query)
+    }
+
+    /// Whether `asset` passes every structured filter of this query
+    pub fn matches(&self, asset: &Asset) -> bool {
+        self.filters.iter().all(|filter| filter.matches(asset))
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, asset: &Asset) -> bool {
+        match self {
+            Filter::Extension(ext) => extension(&asset.filename).is_some_and(|e| e.eq_ignore_ascii_case(ext)),
+            Filter::Size(comparison, size) => comparison.compare(asset.size, *size),
+            Filter::Fetched(fetched) => asset.already_fetched == *fetched,
+            Filter::Table(table) => asset.table.eq_ignore_ascii_case(table),
+            Filter::Crc(crc) => asset.crc == *crc,
+            Filter::Regex(regex) => regex.is_match(&asset.filename),
+            Filter::Exact(needle) => asset.filename.contains(needle.as_str()),
+        }
+    }
+}
+
+impl Comparison {
+    fn compare(self, lhs: i64, rhs: i64) -> bool {
+        match self {
+            Comparison::Less => lhs < rhs,
+            Comparison::LessOrEqual => lhs <= rhs,
+            Comparison::Equal => lhs == rhs,
+            Comparison::GreaterOrEqual => lhs >= rhs,
+            Comparison::Greater => lhs > rhs,
+        }
+    }
+}
+
+/// Returns the extension of `filename` without the leading dot
+pub fn extension(filename: &str) -> Option<&str> {
+    let name = filename.rsplit('/').next().unwrap_or(filename);
+    name.rsplit_once('.').map(|(_, ext)| ext).filter(|ext| !ext.is_empty())
+}
+
+/// Splits the input on whitespace while keeping double-quoted sections together.
+/// The returned flag tells whether the token started with a quote.
+fn tokenize(input: &str) -> Vec<(String, bool)> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut quoted = false;
+    let mut started = false;
+
+    for c in input.chars() {
+        match c {
+            '"' => {
+                if !started {
+                    quoted = true;
+                }
+                in_quotes = !in_quotes;
+                started = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if started {
+                    tokens.push((std::mem::take(&mut current), quoted));
+                }
+                quoted = false;
+                started = false;
+            }
+            c => {
+                current.push(c);
+                started = true;
+            }
+        }
+    }
+
+    if started {
+        tokens.push((current, quoted));
+    }
+
+    tokens
+}
+
+fn parse_filter(token: &str) -> Result<Option<Filter>, QueryError> {
+    if let Some(rest) = token.strip_prefix("size") {
+        for (op, comparison) in [
+            (">=", Comparison::GreaterOrEqual),
+            ("<=", Comparison::LessOrEqual),
+            (">", Comparison::Greater),
+            ("<", Comparison::Less),
+            ("=", Comparison::Equal),
+            (":", Comparison::Equal),
+        ] {
+            if let Some(value) = rest.strip_prefix(op) {
+                return Ok(Some(Filter::Size(comparison, parse_size(value)?)));
+            }
+        }
+    }
+
+    let Some((key, value)) = token.split_once(':') else {
+        return Ok(None);
+    };
+
+    let filter = match key.to_ascii_lowercase().as_str() {
+        "ext" => Filter::Extension(value.trim_start_matches('.').to_string()),
+        "fetched" => Filter::Fetched(parse_bool(value)?),
+        "table" => Filter::Table(value.to_string()),
+        "crc" => Filter::Crc(parse_crc(value)?),
+        "re" => Filter::Regex(Regex::new(value)?),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(filter))
+}
+
+/// Parses sizes like `512`, `10KB` or `1.5 GB` (1024 based, same as the list shows them)
+fn parse_size(value: &str) -> Result<i64, QueryError> {
+    let value = value.trim();
+    let split = value.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let multiplier: f64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1.0,
+        "K" | "KB" => 1024.0,
+        "M" | "MB" => 1024.0 * 1024.0,
+        "G" | "GB" => 1024.0 * 1024.0 * 1024.0,
+        _ => return Err(QueryError::InvalidSize(value.to_string())),
+    };
+
+    number
+        .parse::<f64>()
+        .map(|n| (n * multiplier) as i64)
+        .map_err(|_| QueryError::InvalidSize(value.to_string()))
+}
+
+fn parse_crc(value: &str) -> Result<i64, QueryError> {
+    let hex = value.trim_start_matches("0x").trim_start_matches("0X");
+    i64::from_str_radix(hex, 16).map_err(|_| QueryError::InvalidCrc(value.to_string()))
+}
+
+fn parse_bool(value: &str) -> Result<bool, QueryError> {
+    match value.to_ascii_lowercase().as_str() {
+        "yes" | "y" | "true" | "1" => Ok(true),
+        "no" | "n" | "false" | "0" => Ok(false),
+        _ => Err(QueryError::InvalidBool(value.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Query {
+        Query::parse(input).expect(input)
+    }
+
+    fn asset(filename: &str, size: i64, crc: i64) -> Asset {
+        Asset {
+            filename: filename.to_string(),
+            size,
+            crc,
+            table: "_Shared".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn keeps_free_text_for_fuzzy_matching() {
+        let query = parse("  root   wad ");
+        assert!(query.filters.is_empty());
+        assert_eq!(query.text, "root wad");
+
+        let query = parse("unknown:key root");
+        assert!(query.filters.is_empty());
+        assert_eq!(query.text, "unknown:key root");
+    }
+
+    #[test]
+    fn quoted_terms_are_exact() {
+        let query = parse(r#""Root Data.wad" ext:wad"#);
+        assert!(matches!(&query.filters[..], [Filter::Exact(exact), Filter::Extension(ext)] if exact == "Root Data.wad" && ext == "wad"));
+        assert!(query.text.is_empty());
+
+        assert!(query.matches(&asset("Data/Root Data.wad", 0, 0)));
+        assert!(!query.matches(&asset("Data/root data.wad", 0, 0)));
+
+        // A quote inside a term does not make it exact
+        assert_eq!(tokenize(r#"a"b c"d"#), vec![("ab cd".to_string(), false)]);
+        assert_eq!(tokenize(r#""" x"#), vec![(String::new(), true), ("x".to_string(), false)]);
+    }
+
+    #[test]
+    fn parses_size_units() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("512B").unwrap(), 512);
+        assert_eq!(parse_size("10kb").unwrap(), 10 * 1024);
+        assert_eq!(parse_size("5MB").unwrap(), 5 * 1024 * 1024);
+        assert_eq!(parse_size("1.5G").unwrap(), 3 * 512 * 1024 * 1024);
+
+        let query = parse("size>5MB size<=6M");
+        assert!(matches!(
+            &query.filters[..],
+            [
+                Filter::Size(Comparison::Greater, 5_242_880),
+                Filter::Size(Comparison::LessOrEqual, 6_291_456)
+            ]
+        ));
+        assert!(query.matches(&asset("a.wad", 6 * 1024 * 1024, 0)));
+        assert!(!query.matches(&asset("a.wad", 5 * 1024 * 1024, 0)));
+
+        assert!(matches!(parse("size:1KB").filters[..], [Filter::Size(Comparison::Equal, 1024)]));
+        assert!(matches!(
+            parse("size>=2").filters[..],
+            [Filter::Size(Comparison::GreaterOrEqual, 2)]
+        ));
+        assert!(matches!(parse("size<2").filters[..], [Filter::Size(Comparison::Less, 2)]));
+    }
+
+    #[test]
+    fn parses_crc_as_hex() {
+        assert!(matches!(parse("crc:1a2B").filters[..], [Filter::Crc(0x1a2b)]));
+        assert!(matches!(parse("crc:0xFF").filters[..], [Filter::Crc(0xff)]));
+        assert!(parse("crc:ff").matches(&asset("a.wad", 0, 255)));
+    }
+
+    #[test]
+    fn parses_other_filters() {
+        let query = parse("ext:.WAD fetched:no table:_shared");
+        assert!(query.matches(&asset("Data/Root.wad", 0, 0)));
+        assert!(!query.matches(&asset("Data/Root.xml", 0, 0)));
+        assert!(!query.matches(&Asset {
+            already_fetched: true,
+            ..asset("Data/Root.wad", 0, 0)
+        }));
+
+        let query = parse(r"re:^Data/.*\.wad$");
+        assert!(query.matches(&asset("Data/Root.wad", 0, 0)));
+        assert!(!query.matches(&asset("Bin/Data/Root.wad", 0, 0)));
+    }
+
+    #[test]
+    fn rejects_invalid_filters() {
+        assert!(matches!(Query::parse("size>5XB"), Err(QueryError::InvalidSize(size)) if size == "5XB"));
+        assert!(matches!(Query::parse("size>"), Err(QueryError::InvalidSize(_))));
+        assert!(matches!(Query::parse("size>1.2.3"), Err(QueryError::InvalidSize(_))));
+        assert!(matches!(Query::parse("crc:xyz"), Err(QueryError::InvalidCrc(crc)) if crc == "xyz"));
+        assert!(matches!(Query::parse("fetched:maybe"), Err(QueryError::InvalidBool(value)) if value == "maybe"));
+        assert!(matches!(Query::parse("re:("), Err(QueryError::InvalidRegex(_))));
+    }
+
+    #[test]
+    fn finds_extensions() {
+        assert_eq!(extension("Data/Root.wad"), Some("wad"));
+        assert_eq!(extension("Data.d/readme"), None);
+        assert_eq!(extension("Data/trailing."), None);
+    }
+}
@@ -0,0 +1,782 @@
+use crate::{download::DownloadTracker, util::data_dir};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs, io,
+    path::PathBuf,
+    sync::{Arc, Mutex, Weak},
+    time::Duration,
+};
+use tokio::sync::{oneshot, Notify};
+
+/// Downloads running at the same time, every further one waits in the queue
+const MAX_CONCURRENT_DOWNLOADS: usize = 4;
+/// How often changes to a persisted queue are written to disk
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// State of a queued download, as saved to disk
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ItemState {
+    Pending,
+    /// Started before, `offset` bytes were received into the `.part` file
+    Partial {
+        offset: u64,
+    },
+    Failed {
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueItem {
+    pub filename: String,
+    #[serde(flatten)]
+    pub state: ItemState,
+    /// Paused items keep their place, but are not started until resumed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub paused: bool,
+    #[serde(skip)]
+    running: bool,
+    /// The job fetching it. Items of the last session have none until they are queued again, failed ones once their job ended.
+    #[serde(skip)]
+    owner: Option<u64>,
+    /// Its key in `QueueState::order`
+    #[serde(skip)]
+    place: u64,
+}
+
+/// The queue of the last session, see `restore`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SavedQueue {
+    pub revision: String,
+    pub items: Vec<QueueItem>,
+}
+
+/// Decides which download runs next, in the order they were queued. Cloning it is cheap, every clone shares the same queue.
+#[derive(Debug, Clone)]
+pub struct DownloadQueue {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<QueueState>,
+    /// Tells how far running downloads got when the queue is saved
+    downloads: DownloadTracker,
+}
+
+#[derive(Debug)]
+struct QueueState {
+    revision: String,
+    items: HashMap<String, QueueItem>,
+    /// Filenames in queue order, keyed by their place
+    order: BTreeMap<u64, String>,
+    next_place: u64,
+    /// Jobs waiting in `acquire`, handed the cancellation signal of their download once it is their turn
+    waiting: HashMap<String, oneshot::Sender<Arc<Notify>>>,
+    /// Places of the waiting jobs which may start, i.e. are not paused. The first ones get the free slots.
+    ready: BTreeSet<u64>,
+    /// Cancellation signals of the running downloads
+    cancels: HashMap<String, Arc<Notify>>,
+    /// How often the download of a file was cancelled, so jobs waiting for another job's download of it stop as well
+    cancelled: HashMap<String, u64>,
+    running: usize,
+    next_owner: u64,
+    /// Changed since it was last saved
+    dirty: bool,
+}
+
+impl QueueState {
+    /// Items in queue order
+    fn ordered(&self) -> impl Iterator<Item = &QueueItem> {
+        self.order.values().map(|filename| &self.items[filename])
+    }
+
+    fn push(&mut self, mut item: QueueItem) {
+        item.place = self.next_place;
+        self.next_place += 1;
+        self.order.insert(item.place, item.filename.clone());
+        self.items.insert(item.filename.clone(), item);
+    }
+
+    /// Takes `filename` out of the queue, a job waiting for it stops waiting
+    fn remove(&mut self, filename: &str) -> Option<QueueItem> {
+        let item = self.items.remove(filename)?;
+        self.order.remove(&item.place);
+        self.ready.remove(&item.place);
+        self.waiting.remove(filename);
+        Some(item)
+    }
+
+    /// Keeps `ready` in line with the item's job, running and paused state
+    fn refresh(&mut self, filename: &str) {
+        let Some(item) = self.items.get(filename) else {
+            return;
+        };
+
+        match self.waiting.contains_key(filename) && !item.running && !item.paused {
+            true => self.ready.insert(item.place),
+            false => self.ready.remove(&item.place),
+        };
+    }
+}
+
+/// What `DownloadQueue::cancel` did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cancellation {
+    NotQueued,
+    /// It had not started yet and left the queue
+    Removed,
+    /// It is running and was told to stop
+    Stopping,
+}
+
+/// A running download's place in the queue, freed when dropped
+#[derive(Debug)]
+pub struct Slot {
+    queue: DownloadQueue,
+    filename: String,
+    cancelled: Arc<Notify>,
+}
+
+/// Frees the slot if the waiting job is dropped right after it was granted one
+struct Waiter {
+    queue: DownloadQueue,
+    filename: String,
+    granted: oneshot::Receiver<Arc<Notify>>,
+}
+
+fn queue_path() -> PathBuf {
+    data_dir().join("queue.json")
+}
+
+/// The queue left over from the last session, if it was not empty and belongs to `revision`.
+/// A queue of another revision is deleted, its file list is not loaded.
+pub fn restore(revision: &str) -> Option<SavedQueue> {
+    let saved: SavedQueue = match fs::read_to_string(queue_path()) {
+        Ok(json) => match serde_json::from_str(&json) {
+            Ok(saved) => saved,
+            Err(e) => {
+                log::warn!("Ignoring the saved download queue: {e}");
+                return None;
+            }
+        },
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            log::warn!("Failed to read the saved download queue: {e}");
+            return None;
+        }
+    };
+
+    if saved.revision != revision && !saved.items.is_empty() {
+        log::info!(
+            "Discarding {} queued downloads of {}, the latest revision is {revision}",
+            saved.items.len(),
+            saved.revision
+        );
+        discard();
+        return None;
+    }
+
+    (!saved.items.is_empty()).then_some(saved)
+}
+
+/// Deletes the saved queue
+pub fn discard() {
+    if let Err(e) = fs::remove_file(queue_path()) {
+        if e.kind() != io::ErrorKind::NotFound {
+            log::warn!("Failed to delete the saved download queue: {e}");
+        }
+    }
+}
+
+impl DownloadQueue {
+    pub fn new(revision: &str, downloads: DownloadTracker) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(QueueState {
+                    revision: revision.to_string(),
+                    items: HashMap::new(),
+                    order: BTreeMap::new(),
+                    next_place: 0,
+                    waiting: HashMap::new(),
+                    ready: BTreeSet::new(),
+                    cancels: HashMap::new(),
+                    cancelled: HashMap::new(),
+                    running: 0,
+                    next_owner: 0,
+                    dirty: false,
+                }),
+                downloads,
+            }),
+        }
+    }
+
+    /// Saves the queue to `data/queue.json` shortly after every change, until the last clone is dropped
+    pub fn persist(&self) {
+        let shared = Arc::downgrade(&self.shared);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SAVE_INTERVAL);
+            loop {
+                interval.tick().await;
+                let Some(queue) = Weak::upgrade(&shared).map(|shared| DownloadQueue { shared }) else {
+                    break;
+                };
+
+                let dirty = std::mem::take(&mut queue.shared.state.lock().unwrap().dirty);
+                if dirty {
+                    if let Err(e) = queue.save() {
+                        log::warn!("Failed to save the download queue: {e}");
+                    }
+                }
+            }
+        });
+    }
+
+    /// Writes the queue to disk, with the progress of running downloads. An empty queue deletes the file.
+    pub fn save(&self) -> io::Result<()> {
+        let saved = {
+            let state = self.shared.state.lock().unwrap();
+            let items = state
+                .ordered()
+                .map(|item| {
+                    let mut item = item.clone();
+                    if item.running {
+                        let offset = self.shared.downloads.get(&item.filename).map(|progress| progress.received);
+                        item.state = ItemState::Partial {
+                            offset: offset.unwrap_or_default(),
+                        };
+                    }
+                    item
+                })
+                .collect();
+
+            SavedQueue {
+                revision: state.revision.clone(),
+                items,
+            }
+        };
+
+        if saved.items.is_empty() {
+            discard();
+            return Ok(());
+        }
+
+        // Written next to it and renamed, so a crash while writing does not lose the queue
+        fs::create_dir_all(data_dir())?;
+        let mut partial = queue_path().into_os_string();
+        partial.push(".part");
+        fs::write(&partial, serde_json::to_string_pretty(&saved)?)?;
+        fs::rename(partial, queue_path())
+    }
+
+    /// Puts the items of an earlier session back in their order. They start once their download is queued again.
+    pub fn restore(&self, saved: SavedQueue) {
+        self.update(|state| {
+            for item in saved.items {
+                if !state.items.contains_key(&item.filename) {
+                    state.push(item);
+                }
+            }
+        });
+    }
+
+    /// Removes every item no job is fetching, e.g. the restored ones which were not queued again
+    pub fn discard_restored(&self) {
+        self.update(|state| {
+            let restored: Vec<String> = state
+                .ordered()
+                .filter(|item| item.owner.is_none())
+                .map(|item| item.filename.clone())
+                .collect();
+            for filename in restored {
+                state.remove(&filename);
+            }
+        });
+    }
+
+    /// Adds `filename` to the end of the queue for a new job and returns the job's id. A failed item is retried,
+    /// a restored one keeps its place. `None` if another job is fetching it already.
+    pub fn claim(&self, filename: &str) -> Option<u64> {
+        self.update(|state| {
+            let owner = state.next_owner;
+            match state.items.get_mut(filename) {
+                Some(item) if item.owner.is_some() => return None,
+                Some(item) => {
+                    if matches!(item.state, ItemState::Failed { .. }) {
+                        item.state = ItemState::Pending;
+                    }
+                    item.owner = Some(owner);
+                }
+                None => state.push(QueueItem {
+                    filename: filename.to_string(),
+                    state: ItemState::Pending,
+                    paused: false,
+                    running: false,
+                    owner: Some(owner),
+                    place: 0,
+                }),
+            }
+
+            state.next_owner += 1;
+            Some(owner)
+        })
+    }
+
+    /// The job `owner` ended. Its item leaves the queue, unless the download failed.
+    pub fn unclaim(&self, filename: &str, owner: u64) {
+        self.update(|state| {
+            let Some(item) = state.items.get_mut(filename).filter(|item| item.owner == Some(owner)) else {
+                return;
+            };
+            match item.state {
+                ItemState::Failed { .. } => item.owner = None,
+                _ => {
+                    state.remove(filename);
+                }
+            }
+        });
+    }
+
+    /// Whether a job is fetching `filename`, queued or running
+    pub fn is_active(&self, filename: &str) -> bool {
+        let state = self.shared.state.lock().unwrap();
+        state.items.get(filename).is_some_and(|item| item.owner.is_some())
+    }
+
+    /// Waits until it is `filename`'s turn. `None` if it was cancelled meanwhile.
+    pub async fn acquire(&self, filename: &str) -> Option<Slot> {
+        let (grant, granted) = oneshot::channel();
+        let queued = self.update(|state| {
+            let queued = state.items.get(filename).is_some_and(|item| !item.running);
+            if queued {
+                state.waiting.insert(filename.to_string(), grant);
+                state.refresh(filename);
+            }
+            queued
+        });
+        if !queued {
+            return None;
+        }
+
+        let mut waiter = Waiter {
+            queue: self.clone(),
+            filename: filename.to_string(),
+            granted,
+        };
+        let cancelled = (&mut waiter.granted).await.ok()?;
+
+        Some(Slot {
+            queue: self.clone(),
+            filename: filename.to_string(),
+            cancelled,
+        })
+    }
+
+    /// Removes a queued download, or tells a running one to stop
+    pub fn cancel(&self, filename: &str) -> Cancellation {
+        self.update(|state| {
+            let cancellation = match state.cancels.get(filename) {
+                Some(cancel) => {
+                    cancel.notify_one();
+                    Cancellation::Stopping
+                }
+                None => match state.remove(filename) {
+                    Some(_) => Cancellation::Removed,
+                    None => return Cancellation::NotQueued,
+                },
+            };
+
+            *state.cancelled.entry(filename.to_string()).or_default() += 1;
+            cancellation
+        })
+    }
+
+    /// How often the download of `filename` was cancelled so far
+    pub fn cancellations(&self, filename: &str) -> u64 {
+        let state = self.shared.state.lock().unwrap();
+        state.cancelled.get(filename).copied().unwrap_or_default()
+    }
+
+    /// Pauses or resumes a queued download, returns whether it is paused now
+    pub fn toggle_pause(&self, filename: &str) -> Option<bool> {
+        self.update(|state| {
+            let item = state.items.get_mut(filename).filter(|item| !item.running)?;
+            item.paused = !item.paused;
+            let paused = item.paused;
+            state.refresh(filename);
+            Some(paused)
+        })
+    }
+
+    /// Moves a queued download `delta` places towards the front (negative) or the back of the queue.
+    /// Returns its new position, counting the downloads which did not start yet.
+    pub fn move_by(&self, filename: &str, delta: isize) -> Option<usize> {
+        self.update(|state| {
+            let from = state.items.get(filename).filter(|item| !item.running)?.place;
+            let queued: Vec<u64> = state.ordered().filter(|item| !item.running).map(|item| item.place).collect();
+            let position = queued.iter().position(|&place| place == from)?;
+            let target = position.saturating_add_signed(delta).min(queued.len() - 1);
+
+            // The items in between each move one place the other way
+            let places = &queued[position.min(target)..=position.max(target)];
+            let mut filenames: Vec<String> = places.iter().map(|place| state.order[place].clone()).collect();
+            match target < position {
+                true => filenames.rotate_right(1),
+                false => filenames.rotate_left(1),
+            }
+            for (&place, filename) in places.iter().zip(filenames) {
+                state.ready.remove(&place);
+                state.items.get_mut(&filename)?.place = place;
+                state.order.insert(place, filename.clone());
+            }
+            for place in places {
+                let filename = state.order[place].clone();
+                state.refresh(&filename);
+            }
+            Some(target + 1)
+        })
+    }
+
+    /// Position among the downloads which did not start yet (starting at 1) and the item itself
+    pub fn get(&self, filename: &str) -> Option<(usize, QueueItem)> {
+        let state = self.shared.state.lock().unwrap();
+
+        let item = state.items.get(filename).filter(|item| !item.running)?;
+        let position = state
+            .order
+            .range(..item.place)
+            .filter(|(_, filename)| !state.items[*filename].running)
+            .count();
+        Some((position + 1, item.clone()))
+    }
+
+    /// Applies `change` and hands the free slots to the first waiting jobs
+    fn update<T>(&self, change: impl FnOnce(&mut QueueState) -> T) -> T {
+        let mut state = self.shared.state.lock().unwrap();
+        state.dirty = true;
+        let result = change(&mut state);
+
+        while state.running < MAX_CONCURRENT_DOWNLOADS {
+            let Some(place) = state.ready.pop_first() else {
+                break;
+            };
+            let filename = state.order[&place].clone();
+
+            // A job which is gone in the meantime does not get the slot
+            let Some(grant) = state.waiting.remove(&filename) else {
+                continue;
+            };
+            let cancel = Arc::new(Notify::new());
+            if grant.send(cancel.clone()).is_ok() {
+                state.cancels.insert(filename.clone(), cancel);
+                if let Some(item) = state.items.get_mut(&filename) {
+                    item.running = true;
+                }
+                state.running += 1;
+            }
+        }
+
+        result
+    }
+
+    fn release(&self, filename: &str) {
+        self.update(|state| {
+            state.running -= 1;
+            state.cancels.remove(filename);
+            if let Some(item) = state.items.get_mut(filename) {
+                item.running = false;
+            }
+        });
+    }
+}
+
+impl Slot {
+    /// Completes once the download was cancelled
+    pub async fn cancelled(&self) {
+        self.cancelled.notified().await
+    }
+
+    /// The download is done, it leaves the queue
+    pub fn finish(self) {
+        self.queue.update(|state| {
+            state.remove(&self.filename);
+        });
+    }
+
+    /// The download failed, it stays in the queue with the reason until it is queued again
+    pub fn fail(self, reason: impl ToString) {
+        self.queue.update(|state| {
+            if let Some(item) = state.items.get_mut(&self.filename) {
+                item.state = ItemState::Failed {
+                    reason: reason.to_string(),
+                };
+            }
+        });
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.queue.release(&self.filename);
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        if self.granted.try_recv().is_ok() {
+            self.queue.release(&self.filename);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing::temp_working_dir;
+    use tokio::task::JoinHandle;
+
+    fn queue() -> DownloadQueue {
+        DownloadQueue::new("V_r1.0", DownloadTracker::default())
+    }
+
+    /// Lets the spawned jobs run until they wait again
+    async fn settle() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    /// Claims and starts `filenames`, filling the slots
+    async fn run_all(queue: &DownloadQueue, filenames: &[&str]) -> Vec<Slot> {
+        let mut slots = Vec::new();
+        for filename in filenames {
+            queue.claim(filename).unwrap();
+            slots.push(queue.acquire(filename).await.expect("a slot is free"));
+        }
+        slots
+    }
+
+    fn wait(queue: &DownloadQueue, filename: &str) -> JoinHandle<Option<Slot>> {
+        let (queue, filename) = (queue.clone(), filename.to_string());
+        tokio::spawn(async move { queue.acquire(&filename).await })
+    }
+
+    fn positions(queue: &DownloadQueue, filenames: &[&str]) -> Vec<Option<usize>> {
+        filenames
+            .iter()
+            .map(|filename| queue.get(filename).map(|(position, _)| position))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn grants_slots_in_queue_order() {
+        let queue = queue();
+        let mut slots = run_all(&queue, &["a", "b", "c", "d"]).await;
+        for filename in ["e", "f", "g"] {
+            queue.claim(filename).unwrap();
+        }
+
+        // Waiting in reverse does not change who is next
+        let (g, f, e) = (wait(&queue, "g"), wait(&queue, "f"), wait(&queue, "e"));
+        settle().await;
+        assert!(!e.is_finished() && !f.is_finished() && !g.is_finished());
+        assert_eq!(positions(&queue, &["a", "e", "f", "g"]), [None, Some(1), Some(2), Some(3)]);
+
+        slots.remove(0).finish();
+        settle().await;
+        assert!(e.is_finished() && !f.is_finished() && !g.is_finished());
+        assert_eq!(positions(&queue, &["e", "f", "g"]), [None, Some(1), Some(2)]);
+
+        drop(slots.remove(0));
+        settle().await;
+        assert!(f.is_finished() && !g.is_finished());
+        assert!(e.await.unwrap().is_some() && f.await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn moves_waiting_downloads() {
+        let queue = queue();
+        let mut slots = run_all(&queue, &["a", "b", "c", "d"]).await;
+        for filename in ["e", "f", "g"] {
+            queue.claim(filename).unwrap();
+        }
+
+        assert_eq!(queue.move_by("g", -2), Some(1));
+        assert_eq!(positions(&queue, &["e", "f", "g"]), [Some(2), Some(3), Some(1)]);
+        assert_eq!(queue.move_by("e", 10), Some(3));
+        assert_eq!(positions(&queue, &["e", "f", "g"]), [Some(3), Some(2), Some(1)]);
+        assert_eq!(queue.move_by("f", -10), Some(1));
+        assert_eq!(positions(&queue, &["e", "f", "g"]), [Some(3), Some(1), Some(2)]);
+        assert_eq!(queue.move_by("a", 1), None, "running downloads keep their place");
+        assert_eq!(queue.move_by("x", 1), None);
+
+        let (e, f, g) = (wait(&queue, "e"), wait(&queue, "f"), wait(&queue, "g"));
+        settle().await;
+        slots.remove(0).finish();
+        settle().await;
+        assert!(f.is_finished() && !g.is_finished() && !e.is_finished());
+
+        // Moving a waiting job ahead of the others hands it the next slot
+        assert_eq!(queue.move_by("e", -1), Some(1));
+        slots.remove(0).finish();
+        settle().await;
+        assert!(e.is_finished() && !g.is_finished());
+    }
+
+    #[tokio::test]
+    async fn paused_downloads_keep_their_place_without_starting() {
+        let queue = queue();
+        let slots = run_all(&queue, &["a", "b", "c", "d"]).await;
+        queue.claim("e").unwrap();
+        queue.claim("f").unwrap();
+
+        assert_eq!(queue.toggle_pause("e"), Some(true));
+        assert_eq!(queue.toggle_pause("a"), None, "running downloads cannot be paused");
+        let (e, f) = (wait(&queue, "e"), wait(&queue, "f"));
+        slots.into_iter().for_each(Slot::finish);
+        settle().await;
+        assert!(f.is_finished() && !e.is_finished());
+        assert_eq!(positions(&queue, &["e"]), [Some(1)]);
+
+        assert_eq!(queue.toggle_pause("e"), Some(false));
+        settle().await;
+        assert!(e.is_finished());
+    }
+
+    #[tokio::test]
+    async fn cancelling_a_waiting_download_removes_it() {
+        let queue = queue();
+        let mut slots = run_all(&queue, &["a", "b", "c", "d"]).await;
+        queue.claim("e").unwrap();
+        let e = wait(&queue, "e");
+        settle().await;
+
+        assert_eq!(queue.cancel("e"), Cancellation::Removed);
+        assert!(e.await.unwrap().is_none(), "the waiting job is told it was cancelled");
+        assert!(!queue.is_active("e"));
+        assert_eq!(queue.cancellations("e"), 1);
+
+        // It does not start once a slot is free
+        slots.remove(0).finish();
+        assert_eq!(queue.get("e").map(|(position, _)| position), None);
+        assert_eq!(queue.cancel("e"), Cancellation::NotQueued);
+        assert_eq!(queue.cancellations("e"), 1);
+        assert!(queue.acquire("e").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn cancelling_a_running_download_tells_it_to_stop() {
+        let queue = queue();
+        let owner = queue.claim("a").unwrap();
+        let slot = queue.acquire("a").await.unwrap();
+
+        assert_eq!(queue.cancel("a"), Cancellation::Stopping);
+        tokio::time::timeout(Duration::from_secs(1), slot.cancelled())
+            .await
+            .expect("the slot is cancelled");
+        assert!(queue.is_active("a"), "it stays in the queue until its job ended");
+        assert_eq!(queue.cancel("a"), Cancellation::Stopping);
+        assert_eq!(queue.cancellations("a"), 2);
+
+        drop(slot);
+        queue.unclaim("a", owner);
+        assert!(!queue.is_active("a"));
+        assert_eq!(queue.cancel("a"), Cancellation::NotQueued);
+        assert_eq!(queue.cancellations("a"), 2);
+    }
+
+    #[tokio::test]
+    async fn ignores_duplicate_claims() {
+        let queue = queue();
+        let owner = queue.claim("a").unwrap();
+        assert_eq!(queue.claim("a"), None);
+
+        // A duplicate job notices the cancellation of the first one instead of claiming the file afterwards
+        let seen = queue.cancellations("a");
+        assert_eq!(queue.cancel("a"), Cancellation::Removed);
+        assert_ne!(queue.cancellations("a"), seen);
+
+        queue.unclaim("a", owner);
+        let owner = queue.claim("a").unwrap();
+        queue.unclaim("a", owner + 1);
+        assert!(queue.is_active("a"), "only the owner unclaims it");
+        queue.unclaim("a", owner);
+        assert!(!queue.is_active("a"));
+    }
+
+    #[tokio::test]
+    async fn failed_downloads_stay_until_queued_again() {
+        let queue = queue();
+        queue.claim("a").unwrap();
+        let owner = queue.claim("b").unwrap();
+        queue.claim("c").unwrap();
+        queue.acquire("b").await.unwrap().fail("connection reset");
+        queue.unclaim("b", owner);
+
+        let (position, item) = queue.get("b").unwrap();
+        assert_eq!(position, 2);
+        assert_eq!(
+            item.state,
+            ItemState::Failed {
+                reason: "connection reset".to_string()
+            }
+        );
+        assert!(!queue.is_active("b"));
+
+        queue.claim("b").unwrap();
+        let (position, item) = queue.get("b").unwrap();
+        assert_eq!((position, item.state), (2, ItemState::Pending));
+    }
+
+    #[tokio::test]
+    async fn saves_the_offset_of_running_downloads() {
+        let downloads = DownloadTracker::default();
+        let queue = DownloadQueue::new("V_r1.0", downloads.clone());
+        let _slots = run_all(&queue, &["a"]).await;
+        queue.claim("b").unwrap();
+        queue.claim("c").unwrap();
+        queue.toggle_pause("c");
+        downloads.resume("a", 100, 25);
+        downloads.add_chunk("a", 15);
+
+        let _dir = temp_working_dir();
+        queue.save().unwrap();
+        let saved = restore("V_r1.0").expect("the queue was saved");
+        let items: Vec<_> = saved
+            .items
+            .iter()
+            .map(|item| (item.filename.as_str(), item.state.clone(), item.paused))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                ("a", ItemState::Partial { offset: 40 }, false),
+                ("b", ItemState::Pending, false),
+                ("c", ItemState::Pending, true)
+            ]
+        );
+
+        // The next session keeps the order and offsets of the items queued again
+        let next = DownloadQueue::new("V_r1.0", DownloadTracker::default());
+        next.restore(saved);
+        next.claim("c").unwrap();
+        next.claim("a").unwrap();
+        next.discard_restored();
+        assert_eq!(positions(&next, &["a", "b", "c"]), [Some(1), None, Some(2)]);
+        assert_eq!(next.get("a").unwrap().1.state, ItemState::Partial { offset: 40 });
+        assert!(next.get("c").unwrap().1.paused);
+
+        // A queue of another revision is deleted
+        assert!(restore("V_r2.0").is_none());
+        assert!(!queue_path().exists());
+        queue.save().unwrap();
+
+        // So is an empty one
+        DownloadQueue::new("V_r1.0", DownloadTracker::default()).save().unwrap();
+        assert!(!queue_path().exists());
+        assert!(restore("V_r1.0").is_none());
+    }
+}
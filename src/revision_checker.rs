@@ -7,10 +7,12 @@ use crate::{
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use regex::Regex;
-use std::{io::Cursor, net::ToSocketAddrs};
+use serde::{Deserialize, Serialize};
+use std::{io::Cursor, net::ToSocketAddrs, time::Duration};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::TcpStream,
+    time::timeout,
 };
 
 const BUFFER_SIZE: usize = 256;
@@ -44,8 +46,9 @@ const FOOD_HEADER: [u8; 2] = [0x0D, 0xF0];
 const SESSION_ACCEPT: &str = "0DF02700000000000802220000000000000000000000000000000000000000000000000000000000000000";
 const SERVICE_ID: u8 = 8; // PATCH
 const MESSAGE_ID: u8 = 2; // MSG_LATEST_FILE_LIST_V2
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
 
-#[derive(Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Revision {
     pub list_file_url: String,
     pub url_prefix: String,
@@ -54,29 +57,52 @@ pub struct Revision {
 impl Revision {
     async fn create_stream() -> std::io::Result<TcpStream> {
         let mut ip = format!("{URL}:{PORT}").to_socket_addrs()?;
-
-        TcpStream::connect(&ip.next().unwrap()).await
+        let Some(addr) = ip.next() else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Could not resolve {URL}"),
+            ));
+        };
+
+        match timeout(CONNECT_TIMEOUT, TcpStream::connect(&addr)).await {
+            Ok(stream) => stream,
+            Err(_) => Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                format!("Connecting to {URL} timed out"),
+            )),
+        }
     }
 
     pub async fn check() -> Result<Revision> {
-        let mut stream = Self::create_stream().await?;
-        println!("Successfully connected to {URL}");
+        let stream = Self::connect().await?;
+        Self::handshake(stream).await
+    }
+
+    /// Opens the connection to the patch server
+    pub async fn connect() -> Result<TcpStream> {
+        let stream = Self::create_stream().await?;
+        log::info!("Successfully connected to {URL}");
+
+        Ok(stream)
+    }
 
+    /// Accepts the session on an opened connection and reads the location of the latest file list
+    pub async fn handshake(mut stream: TcpStream) -> Result<Revision> {
         let mut buffer = [0u8; BUFFER_SIZE];
-        stream.read(&mut buffer).await?; // We don't need the SessionOffer
+        let _ = stream.read(&mut buffer).await?; // We don't need the SessionOffer
         buffer = [0u8; BUFFER_SIZE];
 
         stream
             .write_all(&hex_decode(SESSION_ACCEPT, &Endianness::Little).unwrap()[..])
             .await?;
 
-        stream.read(&mut buffer).await?;
+        let _ = stream.read(&mut buffer).await?;
         stream.shutdown().await?;
 
         let mut cursor: Cursor<[u8; BUFFER_SIZE]> = Cursor::new(buffer);
 
         if !cursor.is_food_header().await {
-            eprintln!("Received invalid Header sequence");
+            log::error!("Received invalid Header sequence");
             return Err(anyhow!(RevisionError::InvalidHeaderSequence));
         }
 
@@ -0,0 +1,143 @@
+use crate::errors::UnsafePathError;
+use std::path::PathBuf;
+
+/// Device names Windows reserves in every folder, even with an extension like `con.txt`
+const RESERVED_NAMES: [&str; 24] = [
+    "CON", "PRN", "AUX", "NUL", "COM0", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT0", "LPT1", "LPT2",
+    "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+/// Not allowed in Windows file names. `:` would also address alternate data streams.
+const INVALID_CHARACTERS: [char; 7] = ['<', '>', ':', '"', '|', '?', '*'];
+
+/// Turns a filename from the file list into a relative path which stays inside the folder it is joined onto.
+/// Backslashes count as separators, empty and `.` segments are dropped unless they are the last one. Names which
+/// could escape the folder, name a folder or are not valid on every platform are rejected.
+pub fn asset_path(filename: &str) -> Result<PathBuf, UnsafePathError> {
+    let filename = filename.replace('\\', "/");
+
+    if filename.starts_with('/') {
+        return Err(UnsafePathError::Absolute);
+    }
+    if let [drive, b':', ..] = filename.as_bytes() {
+        if drive.is_ascii_alphabetic() {
+            return Err(UnsafePathError::DriveLetter);
+        }
+    }
+
+    let mut path = PathBuf::new();
+    for segment in filename.split('/') {
+        match segment {
+            "" | "." => continue,
+            ".." => return Err(UnsafePathError::Traversal),
+            _ => {}
+        }
+
+        if let Some(c) = segment.chars().find(|c| c.is_control() || INVALID_CHARACTERS.contains(c)) {
+            return Err(UnsafePathError::InvalidCharacter(c));
+        }
+        // Windows silently drops them, so `a.` and `a` would be the same file
+        if segment.ends_with(['.', ' ']) {
+            return Err(UnsafePathError::TrailingDotOrSpace(segment.to_string()));
+        }
+
+        let stem = segment.split('.').next().unwrap_or_default().trim_end();
+        if RESERVED_NAMES.iter().any(|reserved| stem.eq_ignore_ascii_case(reserved)) {
+            return Err(UnsafePathError::ReservedName(segment.to_string()));
+        }
+
+        path.push(segment);
+    }
+
+    if path.as_os_str().is_empty() {
+        return Err(UnsafePathError::Empty);
+    }
+    if filename.ends_with('/') || filename.ends_with("/.") {
+        return Err(UnsafePathError::TrailingSeparator);
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn rejects(filename: &str) -> UnsafePathError {
+        asset_path(filename).expect_err(filename)
+    }
+
+    #[test]
+    fn accepts_regular_filenames() {
+        assert_eq!(asset_path("Data/GameData/Root.wad").unwrap(), Path::new("Data/GameData/Root.wad"));
+        assert_eq!(asset_path("readme").unwrap(), Path::new("readme"));
+        assert_eq!(asset_path("Bin/..hidden").unwrap(), Path::new("Bin/..hidden"));
+        assert_eq!(asset_path("Data/console.xml").unwrap(), Path::new("Data/console.xml"));
+        assert_eq!(asset_path("Data/COM10.wad").unwrap(), Path::new("Data/COM10.wad"));
+    }
+
+    #[test]
+    fn normalizes_separators() {
+        assert_eq!(asset_path("Data\\GameData\\Root.wad").unwrap(), Path::new("Data/GameData/Root.wad"));
+        assert_eq!(
+            asset_path("./Data//GameData/./Root.wad").unwrap(),
+            Path::new("Data/GameData/Root.wad")
+        );
+    }
+
+    #[test]
+    fn rejects_folder_names() {
+        assert_eq!(rejects("Data/GameData/"), UnsafePathError::TrailingSeparator);
+        assert_eq!(rejects("Data\\GameData\\"), UnsafePathError::TrailingSeparator);
+        assert_eq!(rejects("Data/GameData/."), UnsafePathError::TrailingSeparator);
+        assert_eq!(rejects("Data//"), UnsafePathError::TrailingSeparator);
+        assert_eq!(rejects("./"), UnsafePathError::Empty);
+    }
+
+    #[test]
+    fn rejects_traversal() {
+        assert_eq!(rejects("../evil.dll"), UnsafePathError::Traversal);
+        assert_eq!(rejects("Data/../../evil.dll"), UnsafePathError::Traversal);
+        assert_eq!(rejects("Data/.."), UnsafePathError::Traversal);
+        assert_eq!(rejects("Data\\..\\..\\evil.dll"), UnsafePathError::Traversal);
+        assert_eq!(rejects("./../evil.dll"), UnsafePathError::Traversal);
+    }
+
+    #[test]
+    fn rejects_absolute_paths() {
+        assert_eq!(rejects("/etc/passwd"), UnsafePathError::Absolute);
+        assert_eq!(rejects("\\Windows\\System32\\evil.dll"), UnsafePathError::Absolute);
+        assert_eq!(rejects("\\\\server\\share\\evil.dll"), UnsafePathError::Absolute);
+        assert_eq!(rejects("//server/share/evil.dll"), UnsafePathError::Absolute);
+    }
+
+    #[test]
+    fn rejects_drive_letters() {
+        assert_eq!(rejects("C:\\Windows\\evil.dll"), UnsafePathError::DriveLetter);
+        assert_eq!(rejects("c:/evil.dll"), UnsafePathError::DriveLetter);
+        assert_eq!(rejects("D:evil.dll"), UnsafePathError::DriveLetter);
+        assert_eq!(rejects("Data/C:/evil.dll"), UnsafePathError::InvalidCharacter(':'));
+    }
+
+    #[test]
+    fn rejects_reserved_names() {
+        assert_eq!(rejects("CON"), UnsafePathError::ReservedName("CON".to_string()));
+        assert_eq!(rejects("Data/nul.txt"), UnsafePathError::ReservedName("nul.txt".to_string()));
+        assert_eq!(rejects("com1.wad"), UnsafePathError::ReservedName("com1.wad".to_string()));
+        assert_eq!(rejects("LPT9/file"), UnsafePathError::ReservedName("LPT9".to_string()));
+        assert_eq!(rejects("Data/aux .xml"), UnsafePathError::ReservedName("aux .xml".to_string()));
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        assert_eq!(rejects(""), UnsafePathError::Empty);
+        assert_eq!(rejects("./"), UnsafePathError::Empty);
+        assert_eq!(rejects("Root.wad:stream"), UnsafePathError::InvalidCharacter(':'));
+        assert_eq!(rejects("Data/a*b.wad"), UnsafePathError::InvalidCharacter('*'));
+        assert_eq!(rejects("Data/a\0b.wad"), UnsafePathError::InvalidCharacter('\0'));
+        assert_eq!(
+            rejects("Data/Root.wad."),
+            UnsafePathError::TrailingDotOrSpace("Root.wad.".to_string())
+        );
+        assert_eq!(rejects("Data /Root.wad"), UnsafePathError::TrailingDotOrSpace("Data ".to_string()));
+    }
+}
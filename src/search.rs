@@ -0,0 +1,148 @@
+/// Minimum score a filename needs to show up in the search results
+pub const MIN_SCORE: u8 = 18;
+
+/// Result of matching a query against a single filename.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    /// Same value `fuzzywuzzy::fuzz::ratio` would return for ASCII filenames (0-100)
+    pub score: u8,
+    /// Byte offsets inside the candidate which were matched by the query
+    pub positions: Vec<usize>,
+}
+
+impl FuzzyMatch {
+    pub fn is_match(&self, position: usize) -> bool {
+        self.positions.binary_search(&position).is_ok()
+    }
+}
+
+/// Scores `candidate` against `query` and reports which characters of `candidate` were matched.
+///
+/// This mirrors `fuzzywuzzy::fuzz::ratio`, but keeps the matching blocks around instead of only summing them up.
+/// It compares characters instead of bytes, so non-ASCII filenames are never sliced inside a character.
+pub fn fuzzy_match(query: &str, candidate: &str) -> FuzzyMatch {
+    let query_chars: Vec<char> = query.chars().collect();
+    let candidate_chars: Vec<char> = candidate.chars().collect();
+    let query_is_shorter = query_chars.len() <= candidate_chars.len();
+    let (shorter, longer) = match query_is_shorter {
+        true => (&query_chars, &candidate_chars),
+        false => (&candidate_chars, &query_chars),
+    };
+
+    let blocks = matching_blocks(shorter, longer);
+    let matched: usize = blocks.iter().map(|&(_, _, len)| len).sum();
+    let total_len = (query_chars.len() + candidate_chars.len()) as f32;
+
+    let score = if total_len > 0.0 {
+        (100.0 * (2.0 * matched as f32 / total_len)).round() as u8
+    } else {
+        100
+    };
+
+    let offsets: Vec<usize> = candidate.char_indices().map(|(offset, _)| offset).collect();
+    let mut positions: Vec<usize> = blocks
+        .iter()
+        .flat_map(|&(i, j, len)| {
+            let start = if query_is_shorter { j } else { i };
+            offsets[start..start + len].iter().copied()
+        })
+        .collect();
+    positions.sort_unstable();
+
+    FuzzyMatch { score, positions }
+}
+
+/// Blocks of characters `shorter[i..i + len] == longer[j..j + len]`, found the way Python's `difflib` does:
+/// the longest block first, then recursively the ones left and right of it.
+fn matching_blocks(shorter: &[char], longer: &[char]) -> Vec<(usize, usize, usize)> {
+    let mut queue = vec![(0, shorter.len(), 0, longer.len())];
+    let mut blocks = Vec::new();
+    while let Some((low1, high1, low2, high2)) = queue.pop() {
+        let (i, j, len) = longest_match(shorter, longer, (low1, high1), (low2, high2));
+        if len == 0 {
+            continue;
+        }
+
+        blocks.push((i, j, len));
+        if low1 < i && low2 < j {
+            queue.push((low1, i, low2, j));
+        }
+        if i + len < high1 && j + len < high2 {
+            queue.push((i + len, high1, j + len, high2));
+        }
+    }
+    blocks.sort_unstable();
+    blocks
+}
+
+/// Longest block inside the given ranges. Of equally long ones, the one starting first in `shorter`, then in `longer`.
+fn longest_match(shorter: &[char], longer: &[char], (low1, high1): (usize, usize), (low2, high2): (usize, usize)) -> (usize, usize, usize) {
+    let mut best = (low1, low2, 0);
+    // Length of the block ending at the previous character of `shorter` and each character of `longer`
+    let mut previous = vec![0; high2 - low2 + 1];
+    for (i, a) in (low1..high1).zip(&shorter[low1..high1]) {
+        let mut current = vec![0; high2 - low2 + 1];
+        for (offset, b) in longer[low2..high2].iter().enumerate() {
+            if a != b {
+                continue;
+            }
+
+            let len = previous[offset] + 1;
+            current[offset + 1] = len;
+            if len > best.2 {
+                best = (i + 1 - len, low2 + offset + 1 - len, len);
+            }
+        }
+        previous = current;
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_like_fuzzywuzzy() {
+        for (query, candidate) in [
+            ("root", "Data/GameData/Root.wad"),
+            ("wad", "Root.wad"),
+            ("abc", "xyz"),
+            ("Root.wad", "root"),
+        ] {
+            assert_eq!(
+                fuzzy_match(query, candidate).score,
+                fuzzywuzzy::fuzz::ratio(query, candidate),
+                "{query} in {candidate}"
+            );
+        }
+        assert_eq!(fuzzy_match("", "").score, 100);
+    }
+
+    #[test]
+    fn reports_matched_positions() {
+        let matched = fuzzy_match("root", "Data/Root.wad");
+        assert_eq!(matched.positions, vec![6, 7, 8]);
+        assert!(matched.is_match(6) && !matched.is_match(5));
+    }
+
+    #[test]
+    fn positions_are_char_boundaries() {
+        for (query, candidate) in [
+            ("aé", "xé"),
+            ("é", "e"),
+            ("café", "Data/Café.wad"),
+            ("Ωmega.wad", "Ω/ü/omega.wad"),
+            ("Data/ü", "ü"),
+        ] {
+            let matched = fuzzy_match(query, candidate);
+            for &position in &matched.positions {
+                assert!(candidate.is_char_boundary(position), "{position} in {candidate}");
+            }
+        }
+
+        let matched = fuzzy_match("é", "Data/café.wad");
+        assert_eq!(matched.positions, vec![8]);
+        assert_eq!(&"Data/café.wad"[8..10], "é");
+    }
+}
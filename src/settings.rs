@@ -0,0 +1,171 @@
+use crate::{keymap::Action, util::data_dir};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, fs, io, path::PathBuf};
+
+/// User configuration, read from `data/settings.json`. Every field is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub keys: KeySettings,
+    pub storage: StorageSettings,
+    pub watch: WatchSettings,
+    /// Run on every downloaded asset, in this order
+    pub hooks: Vec<HookSettings>,
+    pub http: HttpSettings,
+    pub downloads: DownloadSettings,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeySettings {
+    pub preset: Preset,
+    /// Replaces the keys of the preset for the given actions, e.g. `"fetch": ["enter", "ctrl+g"]`
+    pub bindings: HashMap<Action, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    #[default]
+    Default,
+    Vim,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StorageSettings {
+    pub backend: StorageBackend,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageBackend {
+    /// A full copy of every revision in `assets/<revision>/`
+    #[default]
+    Folders,
+    /// Every distinct file once in `store/`, see `ObjectStore`
+    ObjectStore,
+}
+
+/// Defaults of `arcane watch`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchSettings {
+    pub interval_secs: u64,
+    pub download: bool,
+    pub hook: Option<String>,
+    pub webhook: Option<String>,
+}
+
+impl Default for WatchSettings {
+    fn default() -> Self {
+        Self {
+            interval_secs: 300,
+            download: false,
+            hook: None,
+            webhook: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownloadSettings {
+    /// Bytes per second all downloads together may receive, 0 for unlimited. Can be changed at runtime.
+    pub rate_limit: u64,
+    /// Mirrors only download within this daily window
+    pub schedule: Option<ScheduleSettings>,
+}
+
+/// Start and end of a daily window as `HH:MM`, the end may be after midnight
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScheduleSettings {
+    pub start: String,
+    pub end: String,
+}
+
+/// Every request to the patch server (and the webhook) goes through one client configured by these
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpSettings {
+    pub connect_timeout_secs: u64,
+    /// Longest wait for the next bytes of a response
+    pub read_timeout_secs: u64,
+    /// Attempts after the first one, for server errors, timeouts and failed connections
+    pub retries: u32,
+    /// Wait before the first retry, doubled for every further one
+    pub retry_delay_ms: u64,
+    /// `http://`, `https://` or `socks5://` URL of a proxy for all requests
+    pub proxy: Option<String>,
+    pub user_agent: String,
+    /// Sent with every request
+    pub headers: HashMap<String, String>,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout_secs: 10,
+            read_timeout_secs: 30,
+            retries: 3,
+            retry_delay_ms: 500,
+            proxy: None,
+            user_agent: "KingsIsle Patcher".to_string(),
+            headers: HashMap::new(),
+        }
+    }
+}
+
+/// A post-download hook, matched by `glob` and/or `extension`. Runs either a built-in `action` or a shell `command`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HookSettings {
+    pub glob: Option<String>,
+    pub extension: Option<String>,
+    pub action: Option<BuiltinAction>,
+    pub command: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuiltinAction {
+    /// Checks size and CRC against the file list
+    Verify,
+    /// Extracts a KIWAD archive into a folder named after it
+    ExtractWad,
+    /// Decompresses a gzip or zlib compressed file next to it
+    Decompress,
+}
+
+impl fmt::Display for BuiltinAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuiltinAction::Verify => write!(f, "verify"),
+            BuiltinAction::ExtractWad => write!(f, "extract_wad"),
+            BuiltinAction::Decompress => write!(f, "decompress"),
+        }
+    }
+}
+
+impl Settings {
+    pub fn path() -> PathBuf {
+        data_dir().join("settings.json")
+    }
+
+    /// Loads the settings, falling back to the defaults if the file is missing or invalid
+    pub fn load() -> Self {
+        match Self::try_load() {
+            Ok(settings) => settings,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                log::error!("Failed to load {}, using the default settings: {e}", Self::path().display());
+                Self::default()
+            }
+        }
+    }
+
+    fn try_load() -> io::Result<Self> {
+        let content = fs::read_to_string(Self::path())?;
+        Ok(serde_json::from_str(&content)?)
+    }
+}
@@ -0,0 +1,90 @@
+use crate::{parser::parser::Asset, query::extension};
+use std::{cmp::Ordering, fmt};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Crc,
+    Fetched,
+    Extension,
+    Table,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+/// Order of the asset list while no free-text search is active
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sort {
+    pub key: SortKey,
+    pub order: SortOrder,
+}
+
+impl SortKey {
+    fn next(self) -> Self {
+        match self {
+            SortKey::Name => SortKey::Size,
+            SortKey::Size => SortKey::Crc,
+            SortKey::Crc => SortKey::Fetched,
+            SortKey::Fetched => SortKey::Extension,
+            SortKey::Extension => SortKey::Table,
+            SortKey::Table => SortKey::Name,
+        }
+    }
+}
+
+impl Sort {
+    pub fn cycle_key(&mut self) {
+        self.key = self.key.next();
+    }
+
+    pub fn reverse(&mut self) {
+        self.order = match self.order {
+            SortOrder::Ascending => SortOrder::Descending,
+            SortOrder::Descending => SortOrder::Ascending,
+        };
+    }
+
+    pub fn compare(&self, a: &Asset, b: &Asset) -> Ordering {
+        let ordering = match self.key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Crc => a.crc.cmp(&b.crc),
+            SortKey::Fetched => a.already_fetched.cmp(&b.already_fetched),
+            SortKey::Extension => extension(&a.filename).cmp(&extension(&b.filename)),
+            SortKey::Table => a.table.cmp(&b.table),
+        }
+        // Falls back to the filename, so equal keys keep a stable order
+        .then_with(|| a.cmp(b));
+
+        match self.order {
+            SortOrder::Ascending => ordering,
+            SortOrder::Descending => ordering.reverse(),
+        }
+    }
+}
+
+impl fmt::Display for Sort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key = match self.key {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Crc => "CRC",
+            SortKey::Fetched => "fetched",
+            SortKey::Extension => "extension",
+            SortKey::Table => "table",
+        };
+        let arrow = match self.order {
+            SortOrder::Ascending => "▲",
+            SortOrder::Descending => "▼",
+        };
+
+        write!(f, "{key} {arrow}")
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{
+    bytes_to_human_readable, format_duration, index_cache, parser::parser::AssetFetcher, revision_checker::Revision, TICK_RATE, VERSION,
+};
+use anyhow::{anyhow, Result};
+use ratatui::{
+    backend::Backend,
+    buffer::Buffer,
+    layout::{Constraint, Layout, Rect},
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Gauge, Paragraph, Widget, Wrap},
+    Terminal,
+};
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+use tui_textarea::{Input, Key};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Stage {
+    Connecting,
+    Handshake,
+    FetchingList,
+    Parsing,
+}
+
+const STAGES: [Stage; 4] = [Stage::Connecting, Stage::Handshake, Stage::FetchingList, Stage::Parsing];
+
+#[derive(Debug, Clone)]
+enum Status {
+    Running,
+    Failed(String),
+}
+
+#[derive(Debug)]
+struct State {
+    stage: Stage,
+    status: Status,
+    offline: bool,
+    received: u64,
+    total: Option<u64>,
+    fetcher: Option<AssetFetcher>,
+}
+
+/// Connects to the patch server and loads the file list in the background, while the loading screen is shown
+#[derive(Debug, Clone)]
+struct Startup {
+    state: Arc<Mutex<State>>,
+}
+
+/// Shows the loading screen until the file list is ready. Returns `None` if the user quit before that.
+pub fn run<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<Option<AssetFetcher>> {
+    let startup = Startup::new();
+    startup.start(false);
+
+    loop {
+        terminal.draw(|f| f.render_widget(&startup, f.size()))?;
+
+        if let Some(fetcher) = startup.take_fetcher() {
+            return Ok(Some(fetcher));
+        }
+
+        if !crossterm::event::poll(TICK_RATE)? {
+            continue;
+        }
+
+        let failed = startup.is_failed();
+        match crossterm::event::read()?.into() {
+            Input { key: Key::Esc, .. } => return Ok(None),
+            Input { key: Key::Char('r'), .. } if failed => startup.start(false),
+            Input { key: Key::Char('o'), .. } if failed => startup.start(true),
+            _ => {}
+        }
+    }
+}
+
+impl Startup {
+    fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                stage: Stage::Connecting,
+                status: Status::Running,
+                offline: false,
+                received: 0,
+                total: None,
+                fetcher: None,
+            })),
+        }
+    }
+
+    /// (Re-)starts loading, either from the patch server or from the cached file list
+    fn start(&self, offline: bool) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.stage = if offline { Stage::Parsing } else { Stage::Connecting };
+            state.status = Status::Running;
+            state.offline = offline;
+            state.received = 0;
+            state.total = None;
+        }
+
+        let startup = self.clone();
+        tokio::spawn(async move {
+            let result = match offline {
+                true => startup.load_offline(),
+                false => startup.load_online().await,
+            };
+
+            let mut state = startup.state.lock().unwrap();
+            match result {
+                Ok(fetcher) => state.fetcher = Some(fetcher),
+                Err(e) => {
+                    log::error!("Failed to load the file list: {e:#}");
+                    state.status = Status::Failed(format!("{e:#}"));
+                }
+            }
+        });
+    }
+
+    async fn load_online(&self) -> Result<AssetFetcher> {
+        let stream = Revision::connect().await?;
+
+        self.set_stage(Stage::Handshake);
+        let revision = Revision::handshake(stream).await?;
+
+        self.set_stage(Stage::FetchingList);
+        let mut fetcher = AssetFetcher::new(revision);
+        let xml_text = fetcher
+            .download_index(|received, total| {
+                let mut state = self.state.lock().unwrap();
+                state.received = received;
+                state.total = total;
+            })
+            .await?;
+
+        self.set_stage(Stage::Parsing);
+        fetcher.parse_downloaded_index(xml_text)?;
+
+        Ok(fetcher)
+    }
+
+    fn load_offline(&self) -> Result<AssetFetcher> {
+        let revision = index_cache::latest()?.ok_or_else(|| anyhow!("No cached file list found, connect at least once first"))?;
+        let (revision, xml_text) = index_cache::load(&revision)?;
+
+        let age = index_cache::age(&revision.revision).map(format_duration).unwrap_or_default();
+        log::warn!("Offline mode: using the cached file list of {} ({age} old)", revision.revision);
+
+        let mut fetcher = AssetFetcher::new(revision);
+        fetcher.parse_index(xml_text)?;
+
+        Ok(fetcher)
+    }
+
+    fn set_stage(&self, stage: Stage) {
+        self.state.lock().unwrap().stage = stage;
+    }
+
+    fn is_failed(&self) -> bool {
+        matches!(self.state.lock().unwrap().status, Status::Failed(_))
+    }
+
+    fn take_fetcher(&self) -> Option<AssetFetcher> {
+        self.state.lock().unwrap().fetcher.take()
+    }
+}
+
+impl Stage {
+    fn description(self, offline: bool) -> &'static str {
+        match self {
+            Stage::Connecting => "Connecting to the patch server",
+            Stage::Handshake => "Requesting the latest revision",
+            Stage::FetchingList => "Fetching the file list",
+            Stage::Parsing if offline => "Loading the cached file list",
+            Stage::Parsing => "Parsing the file list",
+        }
+    }
+}
+
+impl Widget for &Startup {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let state = self.state.lock().unwrap();
+        let failed = matches!(state.status, Status::Failed(_));
+
+        let [_, area, _] = Layout::vertical([Constraint::Fill(1), Constraint::Length(14), Constraint::Fill(1)]).areas(area);
+        let [_, area, _] = Layout::horizontal([Constraint::Fill(1), Constraint::Length(70), Constraint::Fill(1)]).areas(area);
+
+        let block = Block::new()
+            .border_type(BorderType::Rounded)
+            .borders(Borders::all())
+            .title(Line::from(format!(" Arcane (Asset-Fetcher) v{VERSION} ")).centered())
+            .title_bottom(
+                Line::from(match failed {
+                    true => " [R] Retry  [O] Offline  [ESC] Quit ",
+                    false => " Press [ESC] to abort ",
+                })
+                .right_aligned(),
+            );
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let [stages_area, gauge_area, message_area] = Layout::vertical([
+            Constraint::Length(STAGES.len() as u16 + 1),
+            Constraint::Length(1),
+            Constraint::Min(1),
+        ])
+        .margin(1)
+        .areas(inner);
+
+        let stages: Vec<Line> = STAGES
+            .iter()
+            .filter(|stage| !state.offline || **stage == Stage::Parsing)
+            .map(|stage| {
+                let (icon, style) = match stage.cmp(&state.stage) {
+                    std::cmp::Ordering::Less => ("✔", Style::default().green()),
+                    std::cmp::Ordering::Equal if failed => ("✘", Style::default().red()),
+                    std::cmp::Ordering::Equal => ("…", Style::default().yellow()),
+                    std::cmp::Ordering::Greater => (" ", Style::default().dark_gray()),
+                };
+
+                Line::from(vec![
+                    Span::styled(format!(" {icon} "), style),
+                    Span::styled(stage.description(state.offline), style),
+                ])
+            })
+            .collect();
+        Paragraph::new(stages).render(stages_area, buf);
+
+        if state.stage == Stage::FetchingList && !failed {
+            let (ratio, label) = match state.total {
+                Some(total) if total > 0 => (
+                    (state.received as f64 / total as f64).clamp(0.0, 1.0),
+                    format!(
+                        "{} / {}",
+                        bytes_to_human_readable(state.received as i64),
+                        bytes_to_human_readable(total as i64)
+                    ),
+                ),
+                _ => (0.0, bytes_to_human_readable(state.received as i64)),
+            };
+
+            Gauge::default()
+                .gauge_style(Style::default().cyan())
+                .ratio(ratio)
+                .label(label)
+                .render(gauge_area, buf);
+        }
+
+        if let Status::Failed(error) = &state.status {
+            Paragraph::new(error.as_str())
+                .style(Style::default().red())
+                .wrap(Wrap { trim: true })
+                .render(message_area, buf);
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use crate::{
+    errors::StoreError,
+    parser::parser::Asset,
+    sanitize::asset_path,
+    settings::{StorageBackend, StorageSettings},
+    util::assets_dir,
+};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Content-addressable storage shared by all revisions. Every distinct file is kept once in `store/objects/`, keyed by its
+/// CRC and size. A revision is a manifest in `store/manifests/` plus its `assets/<revision>/` folder of hard links into the store.
+#[derive(Debug, Clone)]
+pub struct ObjectStore {
+    dir: PathBuf,
+}
+
+/// Maps every filename of a revision to the object holding its content
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    revision: String,
+    created_at: String,
+    objects: BTreeMap<String, String>,
+}
+
+/// Outcome of a garbage collection
+#[derive(Debug, Default)]
+pub struct GcReport {
+    pub kept: Vec<String>,
+    /// Revisions whose manifest and asset folder were removed
+    pub dropped: Vec<String>,
+    pub removed: usize,
+    /// Size of the removed objects which were not hard-linked from anywhere else
+    pub freed: u64,
+    pub remaining: usize,
+}
+
+impl ObjectStore {
+    pub fn open() -> Self {
+        Self {
+            dir: PathBuf::from("store"),
+        }
+    }
+
+    /// The store, if the settings choose it as storage backend
+    pub fn configured(settings: &StorageSettings) -> Option<Self> {
+        match settings.backend {
+            StorageBackend::Folders => None,
+            StorageBackend::ObjectStore => Some(Self::open()),
+        }
+    }
+
+    fn objects_dir(&self) -> PathBuf {
+        self.dir.join("objects")
+    }
+
+    fn manifest_path(&self, revision: &str) -> PathBuf {
+        self.dir.join("manifests").join(format!("{revision}.json"))
+    }
+
+    /// Objects are spread over subfolders named after the first two characters of their key
+    pub fn object_path(&self, asset: &Asset) -> PathBuf {
+        let key = object_key(asset);
+        self.objects_dir().join(&key[..2]).join(key)
+    }
+
+    pub fn contains(&self, asset: &Asset) -> bool {
+        self.object_path(asset).is_file()
+    }
+
+    /// Adds the file at `path`, which has to match `asset`, unless the store has an object with the same content already
+    pub fn insert(&self, path: &Path, asset: &Asset) -> io::Result<()> {
+        let object = self.object_path(asset);
+        if object.exists() {
+            return Ok(());
+        }
+
+        if let Some(parent) = object.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        match fs::hard_link(path, &object) {
+            Ok(()) => Ok(()),
+            // Another download with the same content was faster
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+            Err(_) => fs::copy(path, &object).map(|_| ()),
+        }
+    }
+
+    /// Records which object every asset of `revision` refers to, replacing an earlier manifest
+    pub fn write_manifest<'a>(&self, revision: &str, assets: impl IntoIterator<Item = &'a Asset>) -> Result<(), StoreError> {
+        let manifest = Manifest {
+            revision: revision.to_string(),
+            created_at: Local::now().to_rfc3339(),
+            objects: assets
+                .into_iter()
+                .map(|asset| (asset.filename.clone(), object_key(asset)))
+                .collect(),
+        };
+
+        let path = self.manifest_path(revision);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&manifest)?)?;
+        Ok(())
+    }
+
+    /// Every revision with a manifest, sorted by name
+    pub fn revisions(&self) -> io::Result<Vec<String>> {
+        let dir = self.dir.join("manifests");
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut revisions = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "json") {
+                if let Some(revision) = path.file_stem() {
+                    revisions.push(revision.to_string_lossy().to_string());
+                }
+            }
+        }
+
+        revisions.sort();
+        Ok(revisions)
+    }
+
+    fn load_manifest(&self, revision: &str) -> Result<Manifest, StoreError> {
+        let content = fs::read_to_string(self.manifest_path(revision))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Deletes every object no kept revision refers to. With `keep`, all other revisions are dropped first, removing their
+    /// manifest and `assets/<revision>/` folder. Otherwise, every revision with a manifest is kept.
+    /// With `dry_run`, nothing is deleted and the report shows what would have been.
+    pub fn gc(&self, keep: Option<&[String]>, dry_run: bool) -> Result<GcReport, StoreError> {
+        let revisions = self.revisions()?;
+        let mut report = GcReport::default();
+
+        match keep {
+            Some(keep) => {
+                if let Some(unknown) = keep.iter().find(|revision| !revisions.contains(revision)) {
+                    return Err(StoreError::UnknownRevision(unknown.clone()));
+                }
+                let (kept, dropped) = revisions.into_iter().partition(|revision| keep.contains(revision));
+                report.kept = kept;
+                report.dropped = dropped;
+            }
+            None => report.kept = revisions,
+        }
+
+        let mut referenced = HashSet::new();
+        for revision in &report.kept {
+            referenced.extend(self.load_manifest(revision)?.objects.into_values());
+        }
+
+        if !dry_run {
+            for revision in &report.dropped {
+                fs::remove_file(self.manifest_path(revision))?;
+                let view = assets_dir(revision);
+                if view.exists() {
+                    fs::remove_dir_all(view)?;
+                }
+                log::info!("Dropped {revision} from the object store");
+            }
+        }
+
+        // A dry run leaves the folders of the dropped revisions in place, so their links must not keep objects counted as used
+        let mut dropped_links: HashMap<String, u64> = HashMap::new();
+        if dry_run {
+            for revision in &report.dropped {
+                let view = assets_dir(revision);
+                for (filename, key) in self.load_manifest(revision)?.objects {
+                    if asset_path(&filename).is_ok_and(|path| view.join(path).is_file()) {
+                        *dropped_links.entry(key).or_default() += 1;
+                    }
+                }
+            }
+        }
+
+        let objects_dir = self.objects_dir();
+        if !objects_dir.exists() {
+            return Ok(report);
+        }
+
+        for shard in fs::read_dir(objects_dir)? {
+            let shard = shard?.path();
+            if !shard.is_dir() {
+                continue;
+            }
+
+            for object in fs::read_dir(&shard)? {
+                let object = object?;
+                let key = object.file_name().to_string_lossy().to_string();
+                if referenced.contains(&key) {
+                    report.remaining += 1;
+                    continue;
+                }
+
+                report.removed += 1;
+                // A file still linked from elsewhere, e.g. an `assets/<revision>/` folder without a manifest, keeps its space
+                let metadata = object.metadata()?;
+                let links = link_count(&metadata).saturating_sub(dropped_links.get(&key).copied().unwrap_or_default());
+                if links <= 1 {
+                    report.freed += metadata.len();
+                }
+                if !dry_run {
+                    fs::remove_file(object.path())?;
+                }
+            }
+
+            if !dry_run {
+                // Only succeeds once the shard is empty
+                let _ = fs::remove_dir(&shard);
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// Number of hard links to a file, 1 where the platform does not tell
+#[cfg(unix)]
+fn link_count(metadata: &fs::Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::nlink(metadata)
+}
+
+#[cfg(not(unix))]
+fn link_count(_: &fs::Metadata) -> u64 {
+    1
+}
+
+/// CRC and size identify the content of an asset, e.g. `1a2b3c4d-20480`
+fn object_key(asset: &Asset) -> String {
+    format!("{:08x}-{}", asset.crc as u32, asset.size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::testing::temp_working_dir;
+
+    fn asset(filename: &str, size: i64, crc: i64) -> Asset {
+        Asset {
+            filename: filename.to_string(),
+            size,
+            crc,
+            ..Default::default()
+        }
+    }
+
+    /// Saves `assets` to the folder of `revision`, adds them to the store and writes the manifest
+    fn add_revision(store: &ObjectStore, revision: &str, assets: &[Asset]) {
+        for asset in assets {
+            let path = assets_dir(revision).join(&asset.filename);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, vec![b'x'; asset.size as usize]).unwrap();
+            store.insert(&path, asset).unwrap();
+        }
+        store.write_manifest(revision, assets).unwrap();
+    }
+
+    fn gc(store: &ObjectStore, keep: Option<&[&str]>, dry_run: bool) -> GcReport {
+        let keep: Option<Vec<String>> = keep.map(|keep| keep.iter().map(|revision| revision.to_string()).collect());
+        store.gc(keep.as_deref(), dry_run).unwrap()
+    }
+
+    #[test]
+    fn keeps_referenced_objects() {
+        let _dir = temp_working_dir();
+        let store = ObjectStore::open();
+        assert_eq!(gc(&store, None, false).removed, 0);
+
+        let shared = asset("Data/shared.wad", 4, 1);
+        add_revision(&store, "V_r1", &[shared.clone(), asset("Data/old.wad", 8, 2)]);
+        add_revision(&store, "V_r2", &[shared, asset("Data/new.wad", 16, 3)]);
+
+        let report = gc(&store, None, false);
+        assert_eq!(report.kept, ["V_r1", "V_r2"]);
+        assert!(report.dropped.is_empty());
+        assert_eq!((report.removed, report.freed, report.remaining), (0, 0, 3));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn frees_only_unlinked_objects() {
+        let _dir = temp_working_dir();
+        let store = ObjectStore::open();
+        let (linked, unlinked) = (asset("Data/linked.wad", 8, 2), asset("Data/unlinked.wad", 16, 3));
+        add_revision(&store, "V_r1", &[asset("Data/kept.wad", 4, 1), linked.clone(), unlinked.clone()]);
+
+        // Both objects lose their manifest entry, only the folder of one of them still links it
+        store.write_manifest("V_r1", &[asset("Data/kept.wad", 4, 1)]).unwrap();
+        fs::remove_file(assets_dir("V_r1").join(&unlinked.filename)).unwrap();
+
+        let report = gc(&store, None, true);
+        assert_eq!((report.removed, report.freed, report.remaining), (2, 16, 1));
+        assert!(store.contains(&linked) && store.contains(&unlinked));
+
+        let report = gc(&store, None, false);
+        assert_eq!((report.removed, report.freed, report.remaining), (2, 16, 1));
+        assert!(!store.contains(&linked) && !store.contains(&unlinked));
+        assert!(assets_dir("V_r1").join(&linked.filename).is_file());
+        assert_eq!(gc(&store, None, false).removed, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn drops_the_revisions_not_kept() {
+        let _dir = temp_working_dir();
+        let store = ObjectStore::open();
+        let shared = asset("Data/shared.wad", 4, 1);
+        add_revision(&store, "V_r1", &[shared.clone(), asset("Data/old.wad", 8, 2)]);
+        add_revision(&store, "V_r2", &[shared, asset("Data/new.wad", 16, 3)]);
+
+        // The dry run leaves the dropped folder in place, but counts its links as gone
+        let report = gc(&store, Some(&["V_r2"]), true);
+        assert_eq!((report.kept, report.dropped), (vec!["V_r2".to_string()], vec!["V_r1".to_string()]));
+        assert_eq!((report.removed, report.freed, report.remaining), (1, 8, 2));
+        assert!(assets_dir("V_r1").is_dir());
+        assert_eq!(store.revisions().unwrap(), ["V_r1", "V_r2"]);
+
+        let report = gc(&store, Some(&["V_r2"]), false);
+        assert_eq!((report.removed, report.freed, report.remaining), (1, 8, 2));
+        assert!(!assets_dir("V_r1").exists());
+        assert!(assets_dir("V_r2").join("Data/shared.wad").is_file());
+        assert_eq!(store.revisions().unwrap(), ["V_r2"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn counts_objects_linked_from_a_kept_folder_as_not_freed() {
+        let _dir = temp_working_dir();
+        let store = ObjectStore::open();
+        let (kept, patched) = (asset("Data/kept.wad", 4, 1), asset("Data/patched.wad", 8, 2));
+        add_revision(&store, "V_r1", std::slice::from_ref(&kept));
+        add_revision(&store, "V_r2", &[kept.clone(), patched.clone()]);
+        // The kept revision no longer refers to the object, but its folder still holds a link to it
+        store.write_manifest("V_r2", &[kept]).unwrap();
+
+        let report = gc(&store, Some(&["V_r2"]), false);
+        assert_eq!((report.removed, report.freed, report.remaining), (1, 0, 1));
+        assert!(!store.contains(&patched));
+        assert!(assets_dir("V_r2").join(&patched.filename).is_file());
+    }
+
+    #[test]
+    fn rejects_unknown_revisions() {
+        let _dir = temp_working_dir();
+        let store = ObjectStore::open();
+        add_revision(&store, "V_r1", &[asset("Data/a.wad", 4, 1)]);
+
+        let keep = ["V_r9".to_string()];
+        assert!(matches!(store.gc(Some(&keep), false), Err(StoreError::UnknownRevision(revision)) if revision == "V_r9"));
+        assert!(store.contains(&asset("Data/a.wad", 4, 1)));
+    }
+}
@@ -0,0 +1,525 @@
+use crate::errors::TextureError;
+use ratatui::{buffer::Buffer, layout::Rect, style::Color, widgets::Widget};
+
+/// Longest side of the thumbnail kept for the preview, larger textures are downscaled when loaded
+const THUMBNAIL_SIZE: u32 = 256;
+/// Largest width or height accepted for DDS textures, bigger values point to a broken header
+const MAX_DIMENSION: u32 = 16384;
+
+/// Decoded texture with 8 bit RGBA pixels, stored row by row
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<[u8; 4]>,
+}
+
+impl Image {
+    /// Decodes a PNG and downscales it to a thumbnail
+    pub fn from_png(bytes: &[u8]) -> Result<Self, TextureError> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        let data = &buffer[..info.buffer_size()];
+
+        let pixels = match info.color_type {
+            png::ColorType::Rgba => data.chunks_exact(4).map(|p| [p[0], p[1], p[2], p[3]]).collect(),
+            png::ColorType::Rgb => data.chunks_exact(3).map(|p| [p[0], p[1], p[2], 255]).collect(),
+            png::ColorType::GrayscaleAlpha => data.chunks_exact(2).map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+            png::ColorType::Grayscale => data.iter().map(|p| [*p, *p, *p, 255]).collect(),
+            png::ColorType::Indexed => return Err(TextureError::UnsupportedFormat("indexed".to_string())),
+        };
+
+        Ok(Self::new(info.width, info.height, pixels)?.thumbnail())
+    }
+
+    /// Decodes the first mipmap of a DXT1/3/5 or uncompressed DDS texture and downscales it to a thumbnail
+    pub fn from_dds(bytes: &[u8]) -> Result<Self, TextureError> {
+        const DDPF_ALPHAPIXELS: u32 = 0x1;
+        const DDPF_FOURCC: u32 = 0x4;
+        const HEADER_SIZE: usize = 128;
+
+        let (Some(height), Some(width), Some(flags)) = (u32_le(bytes, 12), u32_le(bytes, 16), u32_le(bytes, 80)) else {
+            return Err(TextureError::Truncated);
+        };
+        let data = bytes.get(HEADER_SIZE..).ok_or(TextureError::Truncated)?;
+        if width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(TextureError::UnsupportedFormat(format!("{width} × {height}")));
+        }
+
+        let pixels = if flags & DDPF_FOURCC != 0 {
+            let format = bytes.get(84..88).ok_or(TextureError::Truncated)?;
+            match format {
+                b"DXT1" => decode_blocks(data, width, height, 8, decode_dxt1)?,
+                b"DXT2" | b"DXT3" => decode_blocks(data, width, height, 16, decode_dxt3)?,
+                b"DXT4" | b"DXT5" => decode_blocks(data, width, height, 16, decode_dxt5)?,
+                _ => return Err(TextureError::UnsupportedFormat(String::from_utf8_lossy(format).to_string())),
+            }
+        } else {
+            let masks = [92, 96, 100, 104].map(|offset| u32_le(bytes, offset).unwrap_or(0));
+            let alpha = flags & DDPF_ALPHAPIXELS != 0;
+            let bits = u32_le(bytes, 88).ok_or(TextureError::Truncated)?;
+            decode_uncompressed(data, width, height, bits, masks, alpha)?
+        };
+
+        Ok(Self::new(width, height, pixels)?.thumbnail())
+    }
+
+    fn new(width: u32, height: u32, pixels: Vec<[u8; 4]>) -> Result<Self, TextureError> {
+        if width == 0 || height == 0 || pixels.len() < (width * height) as usize {
+            return Err(TextureError::Truncated);
+        }
+
+        Ok(Self { width, height, pixels })
+    }
+
+    fn thumbnail(self) -> Self {
+        let scale = (self.width.max(self.height) as f64 / THUMBNAIL_SIZE as f64).max(1.0);
+        let width = ((self.width as f64 / scale).round() as u32).max(1);
+        let height = ((self.height as f64 / scale).round() as u32).max(1);
+
+        match (width, height) == (self.width, self.height) {
+            true => self,
+            false => self.resize(width, height),
+        }
+    }
+
+    /// Box filter: every target pixel is the average of the source pixels it covers
+    fn resize(&self, width: u32, height: u32) -> Self {
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+
+        for y in 0..height {
+            let (y0, y1) = span(y, height, self.height);
+            for x in 0..width {
+                let (x0, x1) = span(x, width, self.width);
+
+                let mut sum = [0u32; 4];
+                for sy in y0..y1 {
+                    for sx in x0..x1 {
+                        let pixel = self.pixels[(sy * self.width + sx) as usize];
+                        for (total, channel) in sum.iter_mut().zip(pixel) {
+                            *total += channel as u32;
+                        }
+                    }
+                }
+
+                let count = ((y1 - y0) * (x1 - x0)).max(1);
+                pixels.push(sum.map(|total| (total / count) as u8));
+            }
+        }
+
+        Self { width, height, pixels }
+    }
+
+    /// Colour of a pixel, blended onto the black terminal background
+    fn color(&self, x: u32, y: u32) -> Color {
+        let [r, g, b, a] = self.pixels[(y * self.width + x) as usize];
+        let blend = |channel: u8| (channel as u32 * a as u32 / 255) as u8;
+        Color::Rgb(blend(r), blend(g), blend(b))
+    }
+}
+
+/// Draws the image as large as possible while keeping its aspect ratio, two pixels per cell using `▀`
+impl Widget for &Image {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        if area.is_empty() {
+            return;
+        }
+
+        let (max_width, max_height) = (area.width as f64, area.height as f64 * 2.0);
+        let scale = (max_width / self.width as f64).min(max_height / self.height as f64);
+        let width = ((self.width as f64 * scale) as u32).clamp(1, area.width as u32);
+        let height = ((self.height as f64 * scale) as u32).clamp(1, area.height as u32 * 2);
+
+        let image = self.resize(width, height);
+        let left = area.x + (area.width - width as u16) / 2;
+
+        for y in (0..height).step_by(2) {
+            for x in 0..width {
+                let top = image.color(x, y);
+                let bottom = match y + 1 < height {
+                    true => image.color(x, y + 1),
+                    false => Color::Reset,
+                };
+
+                buf.get_mut(left + x as u16, area.y + (y / 2) as u16)
+                    .set_symbol("▀")
+                    .set_fg(top)
+                    .set_bg(bottom);
+            }
+        }
+    }
+}
+
+/// Source range of target pixel `i` when scaling `source` pixels down (or up) to `target`
+fn span(i: u32, target: u32, source: u32) -> (u32, u32) {
+    let start = (i as u64 * source as u64 / target as u64) as u32;
+    let end = ((i as u64 + 1) * source as u64 / target as u64) as u32;
+    (start, end.max(start + 1).min(source))
+}
+
+fn u16_le(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_le(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// Decodes a texture made of 4×4 pixel blocks, `decode` turns one block into its 16 pixels
+fn decode_blocks(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    block_size: usize,
+    decode: fn(&[u8]) -> [[u8; 4]; 16],
+) -> Result<Vec<[u8; 4]>, TextureError> {
+    let (blocks_x, blocks_y) = (width.div_ceil(4), height.div_ceil(4));
+    if data.len() < (blocks_x * blocks_y) as usize * block_size {
+        return Err(TextureError::Truncated);
+    }
+
+    let mut pixels = vec![[0; 4]; (width * height) as usize];
+    for (i, block) in data.chunks_exact(block_size).take((blocks_x * blocks_y) as usize).enumerate() {
+        let (bx, by) = (i as u32 % blocks_x * 4, i as u32 / blocks_x * 4);
+
+        for (j, pixel) in decode(block).into_iter().enumerate() {
+            let (x, y) = (bx + j as u32 % 4, by + j as u32 / 4);
+            if x < width && y < height {
+                pixels[(y * width + x) as usize] = pixel;
+            }
+        }
+    }
+
+    Ok(pixels)
+}
+
+fn rgb565(color: u16) -> [u8; 4] {
+    let r = (color >> 11) & 0x1f;
+    let g = (color >> 5) & 0x3f;
+    let b = color & 0x1f;
+    [(r * 255 / 31) as u8, (g * 255 / 63) as u8, (b * 255 / 31) as u8, 255]
+}
+
+fn mix(a: [u8; 4], b: [u8; 4], weight_a: u32, weight_b: u32) -> [u8; 4] {
+    let total = weight_a + weight_b;
+    [0, 1, 2, 3].map(|i| ((a[i] as u32 * weight_a + b[i] as u32 * weight_b) / total) as u8)
+}
+
+/// Colour part shared by all DXT formats. DXT1 uses the three colour mode with transparency if `c0 <= c1`.
+fn decode_color_block(block: &[u8], dxt1: bool) -> [[u8; 4]; 16] {
+    let (c0, c1) = (u16_le(block, 0), u16_le(block, 2));
+    let (a, b) = (rgb565(c0), rgb565(c1));
+
+    let palette = match !dxt1 || c0 > c1 {
+        true => [a, b, mix(a, b, 2, 1), mix(a, b, 1, 2)],
+        false => [a, b, mix(a, b, 1, 1), [0, 0, 0, 0]],
+    };
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    std::array::from_fn(|i| palette[((indices >> (i * 2)) & 0x3) as usize])
+}
+
+fn decode_dxt1(block: &[u8]) -> [[u8; 4]; 16] {
+    decode_color_block(block, true)
+}
+
+/// Explicit 4 bit alpha per pixel, followed by a colour block
+fn decode_dxt3(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut pixels = decode_color_block(&block[8..], false);
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let alpha = (block[i / 2] >> ((i % 2) * 4)) & 0xf;
+        pixel[3] = alpha * 17;
+    }
+    pixels
+}
+
+/// Two alpha endpoints with 3 bit indices per pixel, followed by a colour block
+fn decode_dxt5(block: &[u8]) -> [[u8; 4]; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let alphas: [u8; 8] = match a0 > a1 {
+        true => std::array::from_fn(|i| match i {
+            0 => a0 as u8,
+            1 => a1 as u8,
+            _ => (((8 - i as u32) * a0 + (i as u32 - 1) * a1) / 7) as u8,
+        }),
+        false => std::array::from_fn(|i| match i {
+            0 => a0 as u8,
+            1 => a1 as u8,
+            6 => 0,
+            7 => 255,
+            _ => (((6 - i as u32) * a0 + (i as u32 - 1) * a1) / 5) as u8,
+        }),
+    };
+
+    let indices = block[2..8].iter().rev().fold(0u64, |acc, byte| (acc << 8) | *byte as u64);
+    let mut pixels = decode_color_block(&block[8..], false);
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        pixel[3] = alphas[((indices >> (i * 3)) & 0x7) as usize];
+    }
+    pixels
+}
+
+/// Uncompressed pixels, every channel described by a bit mask
+fn decode_uncompressed(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    bits: u32,
+    masks: [u32; 4],
+    alpha: bool,
+) -> Result<Vec<[u8; 4]>, TextureError> {
+    if !matches!(bits, 8 | 16 | 24 | 32) || masks[..3].iter().all(|mask| *mask == 0) {
+        return Err(TextureError::UnsupportedFormat(format!("{bits} bit")));
+    }
+
+    let bytes_per_pixel = (bits / 8) as usize;
+    let pixel_count = (width * height) as usize;
+    if data.len() < pixel_count * bytes_per_pixel {
+        return Err(TextureError::Truncated);
+    }
+
+    let channel = |value: u32, mask: u32| -> u8 {
+        if mask == 0 {
+            return 255;
+        }
+        let max = (mask >> mask.trailing_zeros()) as u64;
+        (((value & mask) >> mask.trailing_zeros()) as u64 * 255 / max) as u8
+    };
+
+    Ok(data
+        .chunks_exact(bytes_per_pixel)
+        .take(pixel_count)
+        .map(|pixel| {
+            let value = pixel.iter().rev().fold(0u32, |acc, byte| (acc << 8) | *byte as u32);
+            let a = match alpha {
+                true => channel(value, masks[3]),
+                false => 255,
+            };
+            [channel(value, masks[0]), channel(value, masks[1]), channel(value, masks[2]), a]
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+    /// A DDS file with the given pixel format, followed by `data`
+    fn dds(width: u32, height: u32, fourcc: Option<&[u8; 4]>, bits: u32, masks: [u32; 4], data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0; 128];
+        bytes[..4].copy_from_slice(b"DDS ");
+        bytes[4..8].copy_from_slice(&124u32.to_le_bytes());
+        bytes[12..16].copy_from_slice(&height.to_le_bytes());
+        bytes[16..20].copy_from_slice(&width.to_le_bytes());
+        let flags: u32 = match fourcc {
+            Some(fourcc) => {
+                bytes[84..88].copy_from_slice(fourcc);
+                0x4
+            }
+            None => 0x40 | if masks[3] != 0 { 0x1 } else { 0 },
+        };
+        bytes[80..84].copy_from_slice(&flags.to_le_bytes());
+        bytes[88..92].copy_from_slice(&bits.to_le_bytes());
+        for (i, mask) in masks.iter().enumerate() {
+            bytes[92 + i * 4..96 + i * 4].copy_from_slice(&mask.to_le_bytes());
+        }
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    /// Red and blue endpoints, the first four pixels use the palette entries 0 to 3 and all others entry 0
+    const DXT1_BLOCK: [u8; 8] = [0x00, 0xf8, 0x1f, 0x00, 0b11_10_01_00, 0, 0, 0];
+
+    #[test]
+    fn decodes_dxt1_blocks() {
+        let pixels = decode_dxt1(&DXT1_BLOCK);
+        assert_eq!(pixels[..4], [RED, BLUE, [170, 0, 85, 255], [85, 0, 170, 255]]);
+        assert!(pixels[4..].iter().all(|pixel| *pixel == RED));
+
+        // With the endpoints swapped, the block uses three colours and transparency
+        let pixels = decode_dxt1(&[0x1f, 0x00, 0x00, 0xf8, 0b11_10_01_00, 0, 0, 0]);
+        assert_eq!(pixels[..4], [BLUE, RED, [127, 0, 127, 255], [0, 0, 0, 0]]);
+    }
+
+    #[test]
+    fn decodes_dxt3_alpha() {
+        let mut block = [0; 16];
+        block[0] = 0xf0;
+        block[1] = 0x08;
+        block[8..].copy_from_slice(&DXT1_BLOCK);
+
+        let pixels = decode_dxt3(&block);
+        assert_eq!(pixels[0], [255, 0, 0, 0]);
+        assert_eq!(pixels[1], [0, 0, 255, 255]);
+        assert_eq!(pixels[2][3], 136);
+        assert_eq!(pixels[3][3], 0);
+    }
+
+    #[test]
+    fn decodes_dxt5_alpha() {
+        // Indices 0, 1, 2 and 7 for the first four pixels, 3 bits each
+        let indices: u64 = 1 << 3 | 2 << 6 | 7 << 9;
+        let mut block = [0; 16];
+        block[..2].copy_from_slice(&[255, 0]);
+        block[2..8].copy_from_slice(&indices.to_le_bytes()[..6]);
+        block[8..12].copy_from_slice(&[0xff, 0xff, 0x00, 0x00]);
+
+        let pixels = decode_dxt5(&block);
+        assert_eq!(pixels[..4], [WHITE, [255, 255, 255, 0], [255, 255, 255, 218], [255, 255, 255, 36]]);
+        assert!(pixels[4..].iter().all(|pixel| *pixel == WHITE));
+
+        // With `a0 <= a1`, there are four interpolated values besides fully transparent and opaque
+        block[..2].copy_from_slice(&[0, 255]);
+        let indices: u64 = 6 | 7 << 3 | 2 << 6;
+        block[2..8].copy_from_slice(&indices.to_le_bytes()[..6]);
+        let alphas: Vec<u8> = decode_dxt5(&block)[..3].iter().map(|pixel| pixel[3]).collect();
+        assert_eq!(alphas, [0, 255, 51]);
+    }
+
+    #[test]
+    fn decodes_dds_textures() {
+        let image = Image::from_dds(&dds(4, 4, Some(b"DXT1"), 0, [0; 4], &DXT1_BLOCK)).unwrap();
+        assert_eq!((image.width, image.height), (4, 4));
+        assert_eq!(image.pixels, decode_dxt1(&DXT1_BLOCK).to_vec());
+
+        // Partial blocks at the right and bottom edge are cut off
+        let blue_block = [0x1f, 0x00, 0x00, 0x00, 0, 0, 0, 0];
+        let image = Image::from_dds(&dds(5, 3, Some(b"DXT1"), 0, [0; 4], &[DXT1_BLOCK, blue_block].concat())).unwrap();
+        assert_eq!((image.width, image.height, image.pixels.len()), (5, 3, 15));
+        assert_eq!(image.pixels[..6], [RED, BLUE, [170, 0, 85, 255], [85, 0, 170, 255], BLUE, RED]);
+        assert_eq!(image.pixels[14], BLUE);
+
+        let mut dxt5 = [0; 16];
+        dxt5[..2].copy_from_slice(&[255, 255]);
+        dxt5[8..].copy_from_slice(&DXT1_BLOCK);
+        let image = Image::from_dds(&dds(4, 4, Some(b"DXT5"), 0, [0; 4], &dxt5)).unwrap();
+        assert_eq!(image.pixels[1], BLUE);
+    }
+
+    #[test]
+    fn decodes_uncompressed_dds_textures() {
+        let bgra = [0x00ff0000, 0x0000ff00, 0x000000ff, 0xff000000];
+        let image = Image::from_dds(&dds(2, 1, None, 32, bgra, &[1, 2, 3, 4, 5, 6, 7, 8])).unwrap();
+        assert_eq!(image.pixels, [[3, 2, 1, 4], [7, 6, 5, 8]]);
+
+        let bgr = [0x00ff0000, 0x0000ff00, 0x000000ff, 0];
+        let image = Image::from_dds(&dds(1, 1, None, 24, bgr, &[1, 2, 3])).unwrap();
+        assert_eq!(image.pixels, [[3, 2, 1, 255]]);
+
+        let rgb565 = [0xf800, 0x07e0, 0x001f, 0];
+        let image = Image::from_dds(&dds(2, 1, None, 16, rgb565, &[0x00, 0xf8, 0xe0, 0x07])).unwrap();
+        assert_eq!(image.pixels, [RED, [0, 255, 0, 255]]);
+
+        let luminance = [0xff, 0, 0, 0];
+        let image = Image::from_dds(&dds(1, 1, None, 8, luminance, &[51])).unwrap();
+        assert_eq!(image.pixels, [[51, 255, 255, 255]]);
+    }
+
+    #[test]
+    fn rejects_unsupported_dds_textures() {
+        let unsupported = |bytes: Vec<u8>| matches!(Image::from_dds(&bytes), Err(TextureError::UnsupportedFormat(_)));
+        assert!(unsupported(dds(4, 4, Some(b"ATI2"), 0, [0; 4], &[0; 16])));
+        assert!(unsupported(dds(1, 1, None, 12, [0xf00, 0xf0, 0xf, 0], &[0; 2])));
+        assert!(unsupported(dds(1, 1, None, 32, [0; 4], &[0; 4])));
+        assert!(unsupported(dds(MAX_DIMENSION + 1, 4, Some(b"DXT1"), 0, [0; 4], &[0; 8])));
+        assert!(unsupported(dds(4, u32::MAX, Some(b"DXT1"), 0, [0; 4], &[0; 8])));
+    }
+
+    #[test]
+    fn rejects_truncated_dds_textures() {
+        let truncated = |bytes: &[u8]| matches!(Image::from_dds(bytes), Err(TextureError::Truncated));
+
+        let full = dds(8, 8, Some(b"DXT5"), 0, [0; 4], &[0; 64]);
+        for len in 0..full.len() {
+            assert!(truncated(&full[..len]), "{len} bytes");
+        }
+        assert!(Image::from_dds(&full).is_ok());
+
+        let full = dds(3, 3, None, 32, [0xff, 0xff00, 0xff0000, 0], &[0; 36]);
+        for len in 0..full.len() {
+            assert!(truncated(&full[..len]), "{len} bytes");
+        }
+
+        assert!(truncated(&dds(0, 4, Some(b"DXT1"), 0, [0; 4], &[0; 8])));
+        assert!(truncated(&dds(MAX_DIMENSION, MAX_DIMENSION, Some(b"DXT1"), 0, [0; 4], &[0; 8])));
+    }
+
+    #[test]
+    fn survives_garbage() {
+        let mut seed = 0x2545f491u32;
+        for _ in 0..2000 {
+            let mut bytes = dds(seed % 9, seed % 7, Some(b"DXT1"), 0, [0; 4], &[]);
+            let len = 128 + (seed % 200) as usize;
+            bytes.resize(len, 0);
+            for byte in bytes.iter_mut().skip(12) {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                // Mostly small values, so the dimensions stay plausible and the decoders get to run
+                *byte = if seed.is_multiple_of(4) { seed as u8 } else { (seed % 3) as u8 };
+            }
+            let _ = Image::from_dds(&bytes);
+        }
+    }
+
+    #[test]
+    fn decodes_pngs() {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.write_header().unwrap().write_image_data(&[255, 0, 0, 0, 0, 255]).unwrap();
+
+        let image = Image::from_png(&bytes).unwrap();
+        assert_eq!(image.pixels, [RED, BLUE]);
+        assert!(Image::from_png(&bytes[..bytes.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn downscales_to_a_thumbnail() {
+        let pixels = (0..512 * 2)
+            .map(|i| if i % 2 == 0 { [0, 0, 0, 255] } else { [200, 100, 50, 255] })
+            .collect();
+        let image = Image::new(512, 2, pixels).unwrap().thumbnail();
+        assert_eq!((image.width, image.height), (THUMBNAIL_SIZE, 1));
+        assert!(image.pixels.iter().all(|pixel| *pixel == [100, 50, 25, 255]));
+
+        let image = Image::new(3, 2, vec![RED; 6]).unwrap().thumbnail();
+        assert_eq!((image.width, image.height), (3, 2));
+    }
+
+    #[test]
+    fn renders_two_pixels_per_cell() {
+        let image = Image::new(2, 3, vec![RED, BLUE, WHITE, [0, 255, 0, 255], [255, 255, 255, 0], RED]).unwrap();
+        let area = Rect::new(0, 0, 2, 2);
+        let mut buf = Buffer::empty(area);
+        image.render(area, &mut buf);
+
+        let cell = buf.get(0, 0);
+        assert_eq!(
+            (cell.symbol(), cell.fg, cell.bg),
+            ("▀", Color::Rgb(255, 0, 0), Color::Rgb(255, 255, 255))
+        );
+        assert_eq!((buf.get(1, 0).fg, buf.get(1, 0).bg), (Color::Rgb(0, 0, 255), Color::Rgb(0, 255, 0)));
+        // Transparent pixels are blended onto black, the odd last row has no bottom half
+        assert_eq!((buf.get(0, 1).fg, buf.get(0, 1).bg), (Color::Rgb(0, 0, 0), Color::Reset));
+
+        // Wide images are centered and scaled down, an empty area is left alone
+        let mut buf = Buffer::empty(Rect::new(0, 0, 10, 1));
+        Image::new(100, 1, vec![WHITE; 100])
+            .unwrap()
+            .render(Rect::new(0, 0, 10, 1), &mut buf);
+        assert!((0..10).all(|x| buf.get(x, 0).fg == Color::Rgb(255, 255, 255)));
+        Image::new(1, 1, vec![WHITE]).unwrap().render(Rect::new(0, 0, 0, 0), &mut buf);
+    }
+}
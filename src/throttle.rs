@@ -0,0 +1,219 @@
+use crate::settings::ScheduleSettings;
+use chrono::{Local, NaiveTime};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Limits the TUI steps through, from fastest to slowest. 0 means unlimited.
+const LIMIT_STEPS: [u64; 9] = [
+    0,
+    20 * 1024 * 1024,
+    10 * 1024 * 1024,
+    5 * 1024 * 1024,
+    2 * 1024 * 1024,
+    1024 * 1024,
+    512 * 1024,
+    256 * 1024,
+    128 * 1024,
+];
+/// How often the download window is checked for opening or closing
+const SCHEDULE_POLL: Duration = Duration::from_secs(30);
+
+/// Bytes per second all downloads together may receive, 0 for unlimited
+static LIMIT: AtomicU64 = AtomicU64::new(0);
+static BUCKET: Mutex<Option<Bucket>> = Mutex::new(None);
+
+/// Token bucket holding up to one second worth of bytes. Downloads may take more than there is, the debt is waited off.
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+pub fn limit() -> u64 {
+    LIMIT.load(Ordering::Relaxed)
+}
+
+pub fn set_limit(bytes_per_sec: u64) {
+    LIMIT.store(bytes_per_sec, Ordering::Relaxed);
+    *BUCKET.lock().unwrap() = None;
+}
+
+/// The next lower limit of the steps, e.g. 5 MB/s after 10 MB/s or after unlimited
+pub fn lower_limit(current: u64) -> u64 {
+    LIMIT_STEPS
+        .iter()
+        .skip(1)
+        .copied()
+        .find(|&step| current == 0 || step < current)
+        .unwrap_or(LIMIT_STEPS[LIMIT_STEPS.len() - 1])
+}
+
+/// The next higher limit of the steps, ending at unlimited
+pub fn raise_limit(current: u64) -> u64 {
+    match current {
+        0 => 0,
+        _ => LIMIT_STEPS.iter().skip(1).rev().copied().find(|&step| step > current).unwrap_or(0),
+    }
+}
+
+/// Waits until `bytes` may be received without exceeding the limit
+pub async fn consume(bytes: u64) {
+    let rate = limit();
+    if rate == 0 {
+        return;
+    }
+
+    let wait = {
+        let mut bucket = BUCKET.lock().unwrap();
+        let bucket = bucket.get_or_insert_with(|| Bucket {
+            tokens: rate as f64,
+            refilled: Instant::now(),
+        });
+
+        let now = Instant::now();
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.refilled).as_secs_f64() * rate as f64).min(rate as f64);
+        bucket.refilled = now;
+        bucket.tokens -= bytes as f64;
+
+        match bucket.tokens < 0.0 {
+            true => Duration::from_secs_f64(-bucket.tokens / rate as f64),
+            false => Duration::ZERO,
+        }
+    };
+
+    if !wait.is_zero() {
+        tokio::time::sleep(wait).await;
+    }
+}
+
+/// Daily window mirrors are allowed to download in, e.g. from 22:00 to 06:00
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schedule {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl Schedule {
+    /// An invalid schedule is logged and ignored
+    pub fn new(settings: &ScheduleSettings) -> Option<Self> {
+        let parse = |time: &str| NaiveTime::parse_from_str(time, "%H:%M");
+
+        match (parse(&settings.start), parse(&settings.end)) {
+            (Ok(start), Ok(end)) if start == end => {
+                log::error!(
+                    "Ignoring the download schedule {}-{}, it would never open",
+                    settings.start,
+                    settings.end
+                );
+                None
+            }
+            (Ok(start), Ok(end)) => Some(Self { start, end }),
+            _ => {
+                log::error!(
+                    "Ignoring the download schedule {}-{}, expected times like 22:00",
+                    settings.start,
+                    settings.end
+                );
+                None
+            }
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.is_open_at(Local::now().time())
+    }
+
+    fn is_open_at(&self, time: NaiveTime) -> bool {
+        match self.start <= self.end {
+            true => self.start <= time && time < self.end,
+            // The window spans midnight
+            false => time >= self.start || time < self.end,
+        }
+    }
+
+    pub async fn wait_until_open(&self) {
+        while !self.is_open() {
+            tokio::time::sleep(SCHEDULE_POLL).await;
+        }
+    }
+
+    pub async fn wait_until_closed(&self) {
+        while self.is_open() {
+            tokio::time::sleep(SCHEDULE_POLL).await;
+        }
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start.format("%H:%M"), self.end.format("%H:%M"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(start: &str, end: &str) -> Option<Schedule> {
+        Schedule::new(&ScheduleSettings {
+            start: start.to_string(),
+            end: end.to_string(),
+        })
+    }
+
+    fn open_at(schedule: &Schedule, times: &[&str]) -> Vec<bool> {
+        times
+            .iter()
+            .map(|time| schedule.is_open_at(NaiveTime::parse_from_str(time, "%H:%M").unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn opens_within_the_day() {
+        let schedule = schedule("09:30", "17:00").unwrap();
+        assert_eq!(
+            open_at(&schedule, &["00:00", "09:29", "09:30", "12:00", "16:59", "17:00", "23:59"]),
+            [false, false, true, true, true, false, false]
+        );
+        assert_eq!(schedule.to_string(), "09:30-17:00");
+    }
+
+    #[test]
+    fn opens_across_midnight() {
+        let schedule = schedule("22:00", "06:00").unwrap();
+        assert_eq!(
+            open_at(&schedule, &["21:59", "22:00", "23:59", "00:00", "05:59", "06:00", "12:00"]),
+            [false, true, true, true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_schedules() {
+        assert_eq!(schedule("22:00", "22:00"), None);
+        assert_eq!(schedule("00:00", "00:00"), None);
+        assert_eq!(schedule("10pm", "06:00"), None);
+        assert_eq!(schedule("22:00", "24:00"), None);
+        assert_eq!(schedule("22:00", ""), None);
+    }
+
+    #[test]
+    fn steps_through_the_limits() {
+        const MB: u64 = 1024 * 1024;
+        assert_eq!(lower_limit(0), 20 * MB);
+        assert_eq!(lower_limit(20 * MB), 10 * MB);
+        assert_eq!(lower_limit(3 * MB), 2 * MB);
+        assert_eq!(lower_limit(128 * 1024), 128 * 1024);
+        assert_eq!(lower_limit(1), 128 * 1024);
+
+        assert_eq!(raise_limit(128 * 1024), 256 * 1024);
+        assert_eq!(raise_limit(3 * MB), 5 * MB);
+        assert_eq!(raise_limit(10 * MB), 20 * MB);
+        assert_eq!(raise_limit(20 * MB), 0);
+        assert_eq!(raise_limit(100 * MB), 0);
+        assert_eq!(raise_limit(0), 0);
+    }
+}
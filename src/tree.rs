@@ -0,0 +1,182 @@
+use crate::parser::parser::Asset;
+use ratatui::widgets::ListState;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashSet},
+    rc::Rc,
+};
+
+/// Aggregated information about a directory and everything below it
+#[derive(Debug, Clone, Default)]
+pub struct DirSummary {
+    /// Full path of the directory, without a trailing slash
+    pub path: String,
+    pub name: String,
+    pub size: i64,
+    pub assets: usize,
+    pub fetched: usize,
+    pub expanded: bool,
+}
+
+#[derive(Debug, Clone)]
+pub enum TreeRow {
+    Dir { depth: usize, summary: DirSummary },
+    File { depth: usize, name: String, asset: Asset },
+}
+
+/// Folds the slash-separated asset paths into expandable directories.
+#[derive(Debug, Clone, Default)]
+pub struct AssetTree {
+    pub state: ListState,
+    expanded: HashSet<String>,
+    /// Rows of the last `rows` call, with the version of the assets they were built from
+    cache: RefCell<Option<(u64, Rc<[TreeRow]>)>>,
+}
+
+#[derive(Default)]
+struct Node<'a> {
+    dirs: BTreeMap<&'a str, Node<'a>>,
+    files: Vec<(&'a str, &'a Asset)>,
+    size: i64,
+    assets: usize,
+    fetched: usize,
+}
+
+impl AssetTree {
+    /// Flattens all currently visible rows of the tree built from `assets`. They are only built again
+    /// once `version`, which has to change whenever `assets` do, or the expanded directories changed.
+    pub fn rows(&self, assets: &[Asset], version: u64) -> Rc<[TreeRow]> {
+        if let Some((cached, rows)) = &*self.cache.borrow() {
+            if *cached == version {
+                return rows.clone();
+            }
+        }
+
+        let rows: Rc<[TreeRow]> = self.build(assets).into();
+        *self.cache.borrow_mut() = Some((version, rows.clone()));
+        rows
+    }
+
+    fn build(&self, assets: &[Asset]) -> Vec<TreeRow> {
+        let mut root = Node::default();
+
+        for asset in assets {
+            let mut node = &mut root;
+            let mut components = asset.filename.split('/').peekable();
+
+            while let Some(component) = components.next() {
+                if components.peek().is_none() {
+                    node.files.push((component, asset));
+                    break;
+                }
+
+                node = node.dirs.entry(component).or_default();
+                node.size += asset.size;
+                node.assets += 1;
+                node.fetched += asset.already_fetched as usize;
+            }
+        }
+
+        let mut rows = Vec::new();
+        self.flatten(&root, "", 0, &mut rows);
+        rows
+    }
+
+    fn flatten(&self, node: &Node, parent: &str, depth: usize, rows: &mut Vec<TreeRow>) {
+        for (name, child) in &node.dirs {
+            let path = match parent {
+                "" => name.to_string(),
+                parent => format!("{parent}/{name}"),
+            };
+            let expanded = self.expanded.contains(&path);
+
+            rows.push(TreeRow::Dir {
+                depth,
+                summary: DirSummary {
+                    path: path.clone(),
+                    name: name.to_string(),
+                    size: child.size,
+                    assets: child.assets,
+                    fetched: child.fetched,
+                    expanded,
+                },
+            });
+
+            if expanded {
+                self.flatten(child, &path, depth + 1, rows);
+            }
+        }
+
+        for (name, asset) in &node.files {
+            rows.push(TreeRow::File {
+                depth,
+                name: name.to_string(),
+                asset: (*asset).clone(),
+            });
+        }
+    }
+
+    pub fn selected<'a>(&self, rows: &'a [TreeRow]) -> Option<&'a TreeRow> {
+        self.state.selected().and_then(|nth| rows.get(nth))
+    }
+
+    pub fn next(&mut self, len: usize) {
+        if len == 0 {
+            return;
+        }
+
+        let to_select = match self.state.selected() {
+            Some(nth) if nth + 1 < len => nth + 1,
+            Some(_) => 0,
+            None => 0,
+        };
+        self.state.select(Some(to_select));
+    }
+
+    pub fn previous(&mut self, len: usize) {
+        if len == 0 {
+            return;
+        }
+
+        let to_select = match self.state.selected() {
+            Some(0) | None => len - 1,
+            Some(nth) => nth - 1,
+        };
+        self.state.select(Some(to_select));
+    }
+
+    pub fn expand(&mut self, path: &str) {
+        self.expanded.insert(path.to_string());
+        *self.cache.get_mut() = None;
+    }
+
+    /// Collapses the selected directory, or jumps to the parent directory if a file or collapsed directory is selected
+    pub fn collapse(&mut self, rows: &[TreeRow]) {
+        let Some(nth) = self.state.selected() else {
+            return;
+        };
+
+        let depth = match rows.get(nth) {
+            Some(TreeRow::Dir { summary, .. }) if summary.expanded => {
+                self.expanded.remove(&summary.path);
+                *self.cache.get_mut() = None;
+                return;
+            }
+            Some(TreeRow::Dir { depth, .. }) | Some(TreeRow::File { depth, .. }) => *depth,
+            None => return,
+        };
+
+        let parent = rows[..nth]
+            .iter()
+            .rposition(|row| matches!(row, TreeRow::Dir { depth: d, .. } if *d + 1 == depth));
+        if let Some(parent) = parent {
+            self.state.select(Some(parent));
+        }
+    }
+}
+
+/// Every asset located somewhere below the directory `path`
+pub fn assets_under<'a>(assets: &'a [Asset], path: &str) -> impl Iterator<Item = &'a Asset> {
+    let prefix = format!("{path}/");
+    assets.iter().filter(move |asset| asset.filename.starts_with(&prefix))
+}
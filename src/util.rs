@@ -1,10 +1,13 @@
+use std::path::PathBuf;
+use tokio::process::Command;
+
 pub enum Endianness {
     Little,
     Big,
 }
 
 pub fn hex_decode(hex_string: &str, endianness: &Endianness) -> Option<Vec<u8>> {
-    if hex_string.len() % 2 != 0 {
+    if !hex_string.len().is_multiple_of(2) {
         return None;
     }
 
@@ -21,3 +24,64 @@ pub fn hex_decode(hex_string: &str, endianness: &Endianness) -> Option<Vec<u8>>
 
     bytes
 }
+
+/// Directory holding everything Arcane stores besides the assets themselves (logs, caches, ...)
+pub fn data_dir() -> PathBuf {
+    PathBuf::from("data")
+}
+
+/// Directory the assets of `revision` are saved to
+pub fn assets_dir(revision: &str) -> PathBuf {
+    PathBuf::from("assets").join(revision)
+}
+
+/// `command` run through the platform's shell, so users can write it like on their command line
+pub fn shell_command(command: &str) -> Command {
+    let (shell, flag) = match cfg!(windows) {
+        true => ("cmd", "/C"),
+        false => ("sh", "-c"),
+    };
+
+    let mut process = Command::new(shell);
+    process.args([flag, command]);
+    process
+}
+
+/// Helpers shared by the tests of several modules
+#[cfg(test)]
+pub mod testing {
+    use std::{
+        env,
+        path::PathBuf,
+        sync::{Mutex, MutexGuard, PoisonError},
+    };
+
+    static WORKING_DIR: Mutex<()> = Mutex::new(());
+
+    /// Makes a fresh temporary directory the working directory until it is dropped. Arcane keeps its files
+    /// relative to the working directory, so tests which touch them run one after the other.
+    pub struct TempWorkingDir {
+        previous: PathBuf,
+        _dir: tempfile::TempDir,
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    pub fn temp_working_dir() -> TempWorkingDir {
+        let lock = WORKING_DIR.lock().unwrap_or_else(PoisonError::into_inner);
+        let previous = env::current_dir().expect("the working directory exists");
+        let dir = tempfile::tempdir().expect("a temporary directory can be created");
+        env::set_current_dir(dir.path()).expect("the temporary directory can be entered");
+
+        TempWorkingDir {
+            previous,
+            _dir: dir,
+            _lock: lock,
+        }
+    }
+
+    impl Drop for TempWorkingDir {
+        fn drop(&mut self) {
+            let _ = env::set_current_dir(&self.previous);
+        }
+    }
+}
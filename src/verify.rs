@@ -0,0 +1,81 @@
+use crate::parser::parser::Asset;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+/// Result of comparing a local file against its entry in the file list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum Verification {
+    Verified,
+    Missing,
+    SizeMismatch { expected: u64, actual: u64 },
+    CrcMismatch { expected: u32, actual: u32 },
+}
+
+impl Verification {
+    pub fn is_verified(self) -> bool {
+        self == Verification::Verified
+    }
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verification::Verified => write!(f, "verified"),
+            Verification::Missing => write!(f, "missing"),
+            Verification::SizeMismatch { expected, actual } => write!(f, "size is {actual} instead of {expected} bytes"),
+            Verification::CrcMismatch { expected, actual } => write!(f, "CRC is {actual:08x} instead of {expected:08x}"),
+        }
+    }
+}
+
+/// Checks size and CRC32 of the file at `path`. The size is compared first, so only complete files are hashed.
+pub fn verify(path: &Path, asset: &Asset) -> io::Result<Verification> {
+    let metadata = match path.metadata() {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Verification::Missing),
+        Err(e) => return Err(e),
+    };
+
+    let expected = asset.size as u64;
+    if metadata.len() != expected {
+        return Ok(Verification::SizeMismatch {
+            expected,
+            actual: metadata.len(),
+        });
+    }
+
+    Ok(compare(asset, metadata.len(), crc32(path)?))
+}
+
+/// Compares the `size` and `crc` of a file against `asset`
+pub fn compare(asset: &Asset, size: u64, crc: u32) -> Verification {
+    let expected = asset.size as u64;
+    if size != expected {
+        return Verification::SizeMismatch { expected, actual: size };
+    }
+
+    let expected = asset.crc as u32;
+    match crc == expected {
+        true => Verification::Verified,
+        false => Verification::CrcMismatch { expected, actual: crc },
+    }
+}
+
+pub fn crc32(path: &Path) -> io::Result<u32> {
+    let mut file = File::open(path)?;
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+        match file.read(&mut buffer)? {
+            0 => return Ok(hasher.finalize()),
+            n => hasher.update(&buffer[..n]),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{errors::WadError, sanitize::asset_path};
+use flate2::read::ZlibDecoder;
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// A file inside a KIWAD archive
+#[derive(Debug, Clone)]
+pub struct WadEntry {
+    pub name: String,
+    offset: u32,
+    pub size: u32,
+    compressed_size: u32,
+    compressed: bool,
+}
+
+/// Read access to a KIWAD archive: `KIWAD`, version, entry count, (since version 2) a flags byte, then the entry table
+pub struct Wad {
+    file: BufReader<File>,
+    /// Length of the archive, which every size read from it has to fit in before anything is allocated for it
+    len: u64,
+    pub entries: Vec<WadEntry>,
+}
+
+impl Wad {
+    pub fn open(path: &Path) -> Result<Self, WadError> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let mut file = BufReader::new(file);
+
+        let mut magic = [0; 5];
+        file.read_exact(&mut magic)?;
+        if &magic != b"KIWAD" {
+            return Err(WadError::NotAWad);
+        }
+
+        let version = read_u32(&mut file)?;
+        let count = read_u32(&mut file)?;
+        if version >= 2 {
+            let mut flags = [0; 1];
+            file.read_exact(&mut flags)?;
+        }
+
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let offset = read_u32(&mut file)?;
+            let size = read_u32(&mut file)?;
+            let compressed_size = read_u32(&mut file)?;
+            let mut compressed = [0; 1];
+            file.read_exact(&mut compressed)?;
+            let _crc = read_u32(&mut file)?;
+
+            let name_len = read_u32(&mut file)?;
+            if name_len as u64 > len {
+                return Err(WadError::Corrupt(format!("an entry name of {name_len} bytes")));
+            }
+            let mut name = vec![0; name_len as usize];
+            file.read_exact(&mut name)?;
+            let name = String::from_utf8_lossy(&name).trim_end_matches('\0').to_string();
+
+            entries.push(WadEntry {
+                name,
+                offset,
+                size,
+                compressed_size,
+                compressed: compressed[0] != 0,
+            });
+        }
+
+        Ok(Self { file, len, entries })
+    }
+
+    /// Uncompressed content of `entry`
+    pub fn read(&mut self, entry: &WadEntry) -> Result<Vec<u8>, WadError> {
+        let stored_size = match entry.compressed {
+            true => entry.compressed_size,
+            false => entry.size,
+        };
+
+        if entry.offset as u64 + stored_size as u64 > self.len {
+            return Err(WadError::Corrupt(format!("{} ends after the end of the archive", entry.name)));
+        }
+
+        self.file.seek(SeekFrom::Start(entry.offset as u64))?;
+        let mut data = vec![0; stored_size as usize];
+        self.file.read_exact(&mut data)?;
+
+        if !entry.compressed {
+            return Ok(data);
+        }
+
+        // The uncompressed size is not checked against anything, so the buffer grows with what is actually decompressed
+        let mut content = Vec::new();
+        ZlibDecoder::new(data.as_slice())
+            .take(entry.size as u64)
+            .read_to_end(&mut content)?;
+        Ok(content)
+    }
+}
+
+/// Extracts every entry of the archive at `path` into `dir`. Returns the number of extracted entries and of entries which
+/// were skipped, because their name would leave `dir` or their data could not be read.
+pub fn extract(path: &Path, dir: &Path) -> Result<(usize, usize), WadError> {
+    let mut wad = Wad::open(path)?;
+    let entries = wad.entries.clone();
+    let (mut extracted, mut skipped) = (0, 0);
+
+    for entry in &entries {
+        let Some(target) = entry_path(dir, &entry.name) else {
+            log::warn!("Skipped {} in {}, its name is unsafe", entry.name, path.display());
+            skipped += 1;
+            continue;
+        };
+
+        // Entries which were never patched are stored as zeroes and fail to decompress
+        let Ok(content) = wad.read(entry) else {
+            skipped += 1;
+            continue;
+        };
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(target, content)?;
+        extracted += 1;
+    }
+
+    Ok((extracted, skipped))
+}
+
+/// `dir` joined with the entry name, unless the name is unsafe, e.g. absolute or climbing up with `..`
+fn entry_path(dir: &Path, name: &str) -> Option<PathBuf> {
+    asset_path(name).ok().map(|path| dir.join(path))
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
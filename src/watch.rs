@@ -0,0 +1,318 @@
+use crate::{
+    format_duration, http, index_cache, mirror,
+    parser::parser::{Asset, AssetFetcher},
+    revision_checker::Revision,
+    settings::{Settings, WatchSettings},
+    util::{data_dir, shell_command},
+    VERSION,
+};
+use anyhow::{bail, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    process::Stdio,
+    time::Duration,
+};
+use tokio::io::AsyncWriteExt;
+
+/// How the watcher reacts to a new revision. Defaults come from the settings, the command line overrides them.
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    pub interval: Duration,
+    /// Download every added or changed asset
+    pub download: bool,
+    /// Shell command run for every new revision
+    pub hook: Option<String>,
+    /// URL every new revision is POSTed to
+    pub webhook: Option<String>,
+}
+
+impl WatchOptions {
+    pub fn new(settings: &WatchSettings) -> Self {
+        Self {
+            interval: Duration::from_secs(settings.interval_secs),
+            download: settings.download,
+            hook: settings.hook.clone(),
+            webhook: settings.webhook.clone(),
+        }
+    }
+}
+
+/// Filenames which differ between two revisions
+#[derive(Debug, Default, Serialize)]
+pub struct RevisionDiff {
+    pub added: Vec<String>,
+    /// Assets whose size or CRC changed
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl RevisionDiff {
+    pub fn between(old: &[Asset], new: &[Asset]) -> Self {
+        let old: HashMap<&str, &Asset> = old.iter().map(|asset| (asset.filename.as_str(), asset)).collect();
+        let mut diff = Self::default();
+
+        for asset in new {
+            match old.get(asset.filename.as_str()) {
+                None => diff.added.push(asset.filename.clone()),
+                Some(old) if old.size != asset.size || old.crc != asset.crc => diff.changed.push(asset.filename.clone()),
+                Some(_) => {}
+            }
+        }
+
+        let new: HashMap<&str, &Asset> = new.iter().map(|asset| (asset.filename.as_str(), asset)).collect();
+        diff.removed = old
+            .keys()
+            .filter(|filename| !new.contains_key(*filename))
+            .map(|filename| filename.to_string())
+            .collect();
+
+        diff.added.sort();
+        diff.changed.sort();
+        diff.removed.sort();
+        diff
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} added, {} changed, {} removed",
+            self.added.len(),
+            self.changed.len(),
+            self.removed.len()
+        )
+    }
+}
+
+/// Sent to the webhook, written to the hook's stdin and appended to `data/revisions.jsonl`
+#[derive(Debug, Serialize)]
+struct NewRevision<'a> {
+    event: &'static str,
+    detected_at: String,
+    revision: &'a Revision,
+    previous: &'a str,
+    diff: &'a RevisionDiff,
+    download: Option<DownloadResult>,
+}
+
+#[derive(Debug, Serialize)]
+struct DownloadResult {
+    downloaded: usize,
+    failed: Vec<String>,
+}
+
+/// The revision the last event was delivered for
+#[derive(Debug, Serialize, Deserialize)]
+struct WatchState {
+    known: String,
+}
+
+fn history_path() -> PathBuf {
+    data_dir().join("revisions.jsonl")
+}
+
+fn state_path() -> PathBuf {
+    data_dir().join("watch.json")
+}
+
+fn load_known() -> Option<String> {
+    let json = fs::read_to_string(state_path()).ok()?;
+    match serde_json::from_str::<WatchState>(&json) {
+        Ok(state) => Some(state.known),
+        Err(e) => {
+            log::warn!("Ignoring {}: {e}", state_path().display());
+            None
+        }
+    }
+}
+
+/// Remembers `revision` as reported, also across restarts
+fn set_known(known: &mut Option<String>, revision: &str) -> io::Result<()> {
+    fs::create_dir_all(data_dir())?;
+    fs::write(
+        state_path(),
+        serde_json::to_string(&WatchState {
+            known: revision.to_string(),
+        })?,
+    )?;
+    *known = Some(revision.to_string());
+    Ok(())
+}
+
+/// Checks for a new revision every `options.interval` until stopped with Ctrl+C. New revisions are compared against the one
+/// the last event was delivered for, or the most recently cached revision on the first run.
+pub async fn watch(options: &WatchOptions, settings: &Settings) -> Result<()> {
+    if options.interval.is_zero() {
+        bail!("The interval has to be at least one second");
+    }
+
+    let mut known = load_known();
+    if known.is_none() {
+        // Saved right away, checking a revision caches its file list and would make it the latest one
+        if let Some(latest) = index_cache::latest()? {
+            set_known(&mut known, &latest)?;
+        }
+    }
+    println!(
+        "Checking for new revisions every {}, the latest known one is {}",
+        format_duration(options.interval),
+        known.as_deref().unwrap_or("unknown")
+    );
+
+    let mut ticker = tokio::time::interval(options.interval);
+    loop {
+        ticker.tick().await;
+
+        if let Err(e) = check(options, settings, &mut known).await {
+            log::warn!("Failed to check for a new revision: {e:#}");
+            eprintln!("[{}] Failed to check for a new revision: {e:#}", timestamp());
+        }
+    }
+}
+
+async fn check(options: &WatchOptions, settings: &Settings, known: &mut Option<String>) -> Result<()> {
+    let revision = Revision::check().await?;
+    if known.as_deref() == Some(revision.revision.as_str()) {
+        return Ok(());
+    }
+
+    // Loading the file list caches it, so the next revision can be compared against this one
+    let mut fetcher = AssetFetcher::new(revision.clone());
+    fetcher.load_index().await?;
+    fetcher.configure(settings)?;
+
+    let Some(previous) = known.clone() else {
+        set_known(known, &revision.revision)?;
+        println!("[{}] Recorded {} as the first known revision", timestamp(), revision.revision);
+        return Ok(());
+    };
+
+    let assets: Vec<Asset> = fetcher.assets.iter().cloned().collect();
+    let diff = RevisionDiff::between(&cached_assets(&previous)?, &assets);
+    log::info!("New revision {} (was {previous}): {}", revision.revision, diff.summary());
+    println!(
+        "[{}] New revision {} (was {previous}): {}",
+        timestamp(),
+        revision.revision,
+        diff.summary()
+    );
+
+    let download = match options.download {
+        true => Some(download(&fetcher, &assets, &diff).await),
+        false => None,
+    };
+
+    let event = NewRevision {
+        event: "new_revision",
+        detected_at: Local::now().to_rfc3339(),
+        revision: &revision,
+        previous: &previous,
+        diff: &diff,
+        download,
+    };
+    let payload = serde_json::to_string(&event)?;
+
+    // Until the event was delivered and recorded, the revision stays unknown and is reported again by the next check
+    let mut delivered = true;
+    if let Some(hook) = &options.hook {
+        if let Err(e) = run_hook(hook, &event, &payload).await {
+            log::warn!("Hook for {} failed: {e:#}", revision.revision);
+            eprintln!("[{}] Hook failed: {e:#}", timestamp());
+            delivered = false;
+        }
+    }
+    if let Some(url) = &options.webhook {
+        if let Err(e) = post_webhook(url, payload.clone()).await {
+            log::warn!("Webhook for {} failed: {e:#}", revision.revision);
+            eprintln!("[{}] Webhook failed: {e:#}", timestamp());
+            delivered = false;
+        }
+    }
+    if delivered {
+        if let Err(e) = record(&payload) {
+            log::warn!("Failed to record {} in {}: {e}", revision.revision, history_path().display());
+            delivered = false;
+        }
+    }
+
+    match delivered {
+        true => set_known(known, &revision.revision)?,
+        false => println!("[{}] {} is reported again with the next check", timestamp(), revision.revision),
+    }
+    Ok(())
+}
+
+fn cached_assets(revision: &str) -> Result<Vec<Asset>> {
+    let (revision, xml_text) = index_cache::load(revision)?;
+    let mut fetcher = AssetFetcher::new(revision);
+    fetcher.parse_index(xml_text)?;
+    Ok(fetcher.assets.into_iter().collect())
+}
+
+/// Mirrors the added and changed assets of the new revision
+async fn download(fetcher: &AssetFetcher, assets: &[Asset], diff: &RevisionDiff) -> DownloadResult {
+    let wanted: HashSet<&String> = diff.added.iter().chain(&diff.changed).collect();
+    let assets: Vec<Asset> = assets.iter().filter(|asset| wanted.contains(&asset.filename)).cloned().collect();
+
+    if let Err(e) = mirror::check_space(fetcher, &assets, None) {
+        log::error!("Not downloading the changes of {}: {e}", fetcher.revision);
+        println!("[{}] Not downloading the changes: {e}", timestamp());
+        return DownloadResult {
+            downloaded: 0,
+            failed: assets.into_iter().map(|asset| asset.filename).collect(),
+        };
+    }
+
+    if let Some(schedule) = fetcher.schedule().filter(|schedule| !schedule.is_open()) {
+        println!("[{}] Waiting for the download window {schedule}", timestamp());
+    }
+    let report = mirror::mirror(fetcher, &assets, None, |_| {}).await;
+    println!("[{}] {}", timestamp(), report.summary());
+
+    DownloadResult {
+        downloaded: report.downloaded + report.skipped + report.reused,
+        failed: report.failed.into_iter().map(|(filename, _)| filename).collect(),
+    }
+}
+
+fn record(payload: &str) -> std::io::Result<()> {
+    fs::create_dir_all(data_dir())?;
+    let mut file = OpenOptions::new().create(true).append(true).open(history_path())?;
+    writeln!(file, "{payload}")
+}
+
+/// Runs `hook` through the shell. The event is passed as JSON on stdin, its key facts as environment variables.
+async fn run_hook(hook: &str, event: &NewRevision<'_>, payload: &str) -> Result<()> {
+    let mut child = shell_command(hook)
+        .env("ARCANE_REVISION", &event.revision.revision)
+        .env("ARCANE_PREVIOUS_REVISION", event.previous)
+        .env("ARCANE_URL_PREFIX", &event.revision.url_prefix)
+        .env("ARCANE_ADDED", event.diff.added.len().to_string())
+        .env("ARCANE_CHANGED", event.diff.changed.len().to_string())
+        .env("ARCANE_REMOVED", event.diff.removed.len().to_string())
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The hook does not have to read its stdin
+        let _ = stdin.write_all(payload.as_bytes()).await;
+    }
+
+    let status = child.wait().await?;
+    if !status.success() {
+        bail!("`{hook}` exited with {status}");
+    }
+    Ok(())
+}
+
+async fn post_webhook(url: &str, payload: String) -> Result<()> {
+    http::client().post_json(url, payload, &format!("Arcane/{VERSION}")).await?;
+    Ok(())
+}
+
+fn timestamp() -> String {
+    Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}